@@ -0,0 +1,114 @@
+use std::process::ExitCode;
+
+use ttt::levels::{self, LevelDef, Personality};
+use ttt::rating::{self, CalibrationSettings, Rating};
+
+const USAGE: &str = "usage: calibrate [--rate levels|personalities|all] [--rounds N] [--games N] [--spread N] [--anchor N] \
+                     [--levels FILE] [--out FILE] [--personalities FILE] [--personalities-out FILE]";
+
+fn read(path: &Option<String>, built_in: &str) -> Result<String, String> {
+    match path {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e)),
+        None => Ok(built_in.to_string()),
+    }
+}
+
+// Only the "rating" lines are rewritten, in file order, so the file keeps its layout.
+fn rewrite_ratings(json: &str, ratings: &[Rating]) -> String {
+    let mut rated = ratings.iter();
+    let lines: Vec<String> = json.lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if !trimmed.starts_with("\"rating\":") { return line.to_string(); }
+            match rated.next() {
+                Some(r) => format!("{}\"rating\": {},", &line[..line.len() - trimmed.len()], (r.rating / 5.0).round() as i64 * 5),
+                None => line.to_string(),
+            }
+        })
+        .collect();
+    lines.join("\n") + "\n"
+}
+
+fn write(out: &Option<String>, json: &str, count: usize) -> Result<(), String> {
+    match out {
+        Some(path) => {
+            std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            eprintln!("Wrote {} ratings to {}", count, path);
+        }
+        None => print!("{}", json),
+    }
+    Ok(())
+}
+
+fn report(names: impl Iterator<Item = String>, ratings: &[Rating]) {
+    for (i, (name, r)) in names.zip(ratings).enumerate() {
+        eprintln!("{:>2} {:<24} {:>5.0} ±{:.0}", i, name, r.rating, r.rd);
+    }
+}
+
+fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut settings = CalibrationSettings::default();
+    let (mut rate_levels, mut rate_personalities) = (true, true);
+    let (mut levels_in, mut levels_out, mut personalities_in, mut personalities_out) = (None, None, None, None);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(USAGE)?;
+        let number = value.parse::<u32>();
+        match (flag.as_str(), number) {
+            ("--rate", _) => {
+                (rate_levels, rate_personalities) = match value.as_str() {
+                    "levels" => (true, false),
+                    "personalities" => (false, true),
+                    "all" => (true, true),
+                    _ => return Err(USAGE.into()),
+                }
+            }
+            ("--rounds", Ok(n)) => settings.rounds = n,
+            ("--games", Ok(n)) => settings.games = n,
+            ("--spread", Ok(n)) => settings.spread = n.max(1),
+            ("--anchor", Ok(n)) => settings.anchor = n as f64,
+            ("--levels", _) => levels_in = Some(value),
+            ("--out", _) => levels_out = Some(value),
+            ("--personalities", _) => personalities_in = Some(value),
+            ("--personalities-out", _) => personalities_out = Some(value),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let json = read(&levels_in, include_str!("../levels.json"))?;
+    let defs = levels::parse_levels(&json).map_err(|e| format!("Invalid levels: {}", e))?;
+    let level_ratings = if rate_levels {
+        let ratings = rating::calibrate(&defs, &settings, |round| eprintln!("Levels round {}/{}", round, settings.rounds));
+        report(defs.iter().map(|d| d.name.clone()), &ratings);
+        write(&levels_out, &rewrite_ratings(&json, &ratings), ratings.len())?;
+        ratings
+    } else {
+        // The levels' ratings as they stand, which is what players are rated against.
+        defs.iter().map(|d| d.rating.map_or_else(Rating::default, Rating::new)).collect()
+    };
+
+    if rate_personalities {
+        let json = read(&personalities_in, include_str!("../personalities.json"))?;
+        let personalities: Vec<Personality> = serde_json::from_str(&json).map_err(|e| format!("Invalid personalities: {}", e))?;
+        let opponents: Vec<(LevelDef, Rating)> = defs.into_iter().zip(level_ratings).collect();
+        let personality_defs: Vec<LevelDef> = personalities.iter().map(|p| p.def.clone()).collect();
+        let ratings = rating::calibrate_against(&personality_defs, &opponents, &settings, |round| {
+            eprintln!("Personalities round {}/{}", round, settings.rounds)
+        });
+        report(personalities.iter().map(|p| p.def.name.clone()), &ratings);
+        // Without a file of their own, personalities go to standard output unless the levels already went there.
+        if personalities_out.is_some() || !rate_levels || levels_out.is_some() {
+            write(&personalities_out, &rewrite_ratings(&json, &ratings), ratings.len())?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::process::ExitCode;
+
+use ttt::puzzle::{self, GeneratorSettings};
+
+const USAGE: &str = "usage: puzzlegen [--games N] [--min N] [--max N] [--branching N] [--out FILE]";
+
+fn main() -> ExitCode {
+    let mut settings = GeneratorSettings::default();
+    let mut out = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = match args.next() {
+            Some(v) => v,
+            None => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        };
+        let number = value.parse::<u32>();
+        match (flag.as_str(), number) {
+            ("--games", Ok(n)) => settings.games = n,
+            ("--min", Ok(n)) => settings.min_moves = n,
+            ("--max", Ok(n)) => settings.max_moves = n,
+            ("--branching", Ok(n)) => settings.max_branching = n as usize,
+            ("--out", _) => out = Some(value),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let puzzles = puzzle::generate(&settings);
+    // One puzzle per line keeps the file readable and its diffs small.
+    let lines: Vec<String> = puzzles.iter().map(|p| format!("  {}", serde_json::to_string(p).unwrap())).collect();
+    let json = format!("[\n{}\n]", lines.join(",\n"));
+    match out {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, json + "\n") {
+                eprintln!("Failed to write {}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+            eprintln!("Wrote {} puzzles to {}", puzzles.len(), path);
+        }
+        None => println!("{}", json),
+    }
+    ExitCode::SUCCESS
+}
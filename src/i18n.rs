@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Cell, Explanation};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    En,
+    Es,
+    De,
+}
+
+impl Locale {
+    pub fn from_tag(tag: &str) -> Self {
+        let lang = tag.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase();
+        match lang.as_str() {
+            "es" => Locale::Es,
+            "de" => Locale::De,
+            _ => Locale::En,
+        }
+    }
+}
+
+const BOARDS_EN: [&str; 9] = [
+    "top-left", "top", "top-right",
+    "left", "center", "right",
+    "bottom-left", "bottom", "bottom-right",
+];
+
+const BOARDS_ES: [&str; 9] = [
+    "superior izquierdo", "superior", "superior derecho",
+    "izquierdo", "central", "derecho",
+    "inferior izquierdo", "inferior", "inferior derecho",
+];
+
+const BOARDS_DE: [&str; 9] = [
+    "oben links", "oben", "oben rechts",
+    "links", "in der Mitte", "rechts",
+    "unten links", "unten", "unten rechts",
+];
+
+fn player(player: Cell, locale: Locale) -> &'static str {
+    match (locale, player) {
+        (Locale::En, Cell::Blue) => "Blue",
+        (Locale::En, Cell::Red) => "Red",
+        (Locale::Es, Cell::Blue) => "Azul",
+        (Locale::Es, Cell::Red) => "Rojo",
+        (Locale::De, Cell::Blue) => "Blau",
+        (Locale::De, Cell::Red) => "Rot",
+        (_, Cell::Empty) => "-",
+    }
+}
+
+fn board(idx: usize, locale: Locale) -> &'static str {
+    let names = match locale {
+        Locale::En => &BOARDS_EN,
+        Locale::Es => &BOARDS_ES,
+        Locale::De => &BOARDS_DE,
+    };
+    names.get(idx).copied().unwrap_or("?")
+}
+
+fn render_en(e: &Explanation) -> String {
+    let l = Locale::En;
+    match *e {
+        Explanation::WinsGame { .. } => "Wins the game!".into(),
+        Explanation::WinsBoardThreatensGame { board: b } =>
+            format!("wins the {} board and threatens the game", board(b, l)),
+        Explanation::WinsBoard { board: b } => format!("wins the {} board", board(b, l)),
+        Explanation::BlocksBoard { board: b, opponent: o } =>
+            format!("blocks {} from winning the {} board", player(o, l), board(b, l)),
+        Explanation::ThreatensGame { .. } => "threatens to win the game".into(),
+        Explanation::CreatesFork { board: b } =>
+            format!("creates two ways to win the {} board", board(b, l)),
+        Explanation::GivesFreeChoice { opponent: o } =>
+            format!("gives {} a free choice of board", player(o, l)),
+        Explanation::SendsToDecidedBoard { board: b, opponent: o } =>
+            format!("sends {} to the {} board, which is already decided", player(o, l), board(b, l)),
+        Explanation::SendsToGameWin { board: b, opponent: o } =>
+            format!("sends {} to the {} board, where they can win the game", player(o, l), board(b, l)),
+        Explanation::SendsToBoardWin { board: b, opponent: o } =>
+            format!("sends {} to the {} board, where they can win immediately", player(o, l), board(b, l)),
+        Explanation::SendsToFork { board: b, opponent: o } =>
+            format!("sends {} to the {} board, where they can set up a fork", player(o, l), board(b, l)),
+        Explanation::ForcesDefense { board: b, opponent: o } =>
+            format!("forces {} to defend the {} board", player(o, l), board(b, l)),
+        Explanation::ForcesIntoFork { board: b, opponent: o } =>
+            format!("forces {} into the {} board, where you have a fork", player(o, l), board(b, l)),
+        Explanation::Positional => "best positional move".into(),
+    }
+}
+
+fn render_es(e: &Explanation) -> String {
+    let l = Locale::Es;
+    match *e {
+        Explanation::WinsGame { .. } => "¡Gana la partida!".into(),
+        Explanation::WinsBoardThreatensGame { board: b } =>
+            format!("gana el tablero {} y amenaza con ganar la partida", board(b, l)),
+        Explanation::WinsBoard { board: b } => format!("gana el tablero {}", board(b, l)),
+        Explanation::BlocksBoard { board: b, opponent: o } =>
+            format!("impide que {} gane el tablero {}", player(o, l), board(b, l)),
+        Explanation::ThreatensGame { .. } => "amenaza con ganar la partida".into(),
+        Explanation::CreatesFork { board: b } =>
+            format!("crea dos formas de ganar el tablero {}", board(b, l)),
+        Explanation::GivesFreeChoice { opponent: o } =>
+            format!("da a {} libre elección de tablero", player(o, l)),
+        Explanation::SendsToDecidedBoard { board: b, opponent: o } =>
+            format!("envía a {} al tablero {}, que ya está decidido", player(o, l), board(b, l)),
+        Explanation::SendsToGameWin { board: b, opponent: o } =>
+            format!("envía a {} al tablero {}, donde puede ganar la partida", player(o, l), board(b, l)),
+        Explanation::SendsToBoardWin { board: b, opponent: o } =>
+            format!("envía a {} al tablero {}, donde puede ganar de inmediato", player(o, l), board(b, l)),
+        Explanation::SendsToFork { board: b, opponent: o } =>
+            format!("envía a {} al tablero {}, donde puede preparar una doble amenaza", player(o, l), board(b, l)),
+        Explanation::ForcesDefense { board: b, opponent: o } =>
+            format!("obliga a {} a defender el tablero {}", player(o, l), board(b, l)),
+        Explanation::ForcesIntoFork { board: b, opponent: o } =>
+            format!("obliga a {} a jugar en el tablero {}, donde tienes una doble amenaza", player(o, l), board(b, l)),
+        Explanation::Positional => "mejor jugada posicional".into(),
+    }
+}
+
+fn render_de(e: &Explanation) -> String {
+    let l = Locale::De;
+    match *e {
+        Explanation::WinsGame { .. } => "Gewinnt das Spiel!".into(),
+        Explanation::WinsBoardThreatensGame { board: b } =>
+            format!("gewinnt das Brett {} und droht, das Spiel zu gewinnen", board(b, l)),
+        Explanation::WinsBoard { board: b } => format!("gewinnt das Brett {}", board(b, l)),
+        Explanation::BlocksBoard { board: b, opponent: o } =>
+            format!("verhindert, dass {} das Brett {} gewinnt", player(o, l), board(b, l)),
+        Explanation::ThreatensGame { .. } => "droht, das Spiel zu gewinnen".into(),
+        Explanation::CreatesFork { board: b } =>
+            format!("schafft zwei Wege, das Brett {} zu gewinnen", board(b, l)),
+        Explanation::GivesFreeChoice { opponent: o } =>
+            format!("lässt {} das Brett frei wählen", player(o, l)),
+        Explanation::SendsToDecidedBoard { board: b, opponent: o } =>
+            format!("schickt {} auf das Brett {}, das bereits entschieden ist", player(o, l), board(b, l)),
+        Explanation::SendsToGameWin { board: b, opponent: o } =>
+            format!("schickt {} auf das Brett {}, wo {} das Spiel gewinnen kann", player(o, l), board(b, l), player(o, l)),
+        Explanation::SendsToBoardWin { board: b, opponent: o } =>
+            format!("schickt {} auf das Brett {}, wo {} sofort gewinnen kann", player(o, l), board(b, l), player(o, l)),
+        Explanation::SendsToFork { board: b, opponent: o } =>
+            format!("schickt {} auf das Brett {}, wo {} eine Gabel aufbauen kann", player(o, l), board(b, l), player(o, l)),
+        Explanation::ForcesDefense { board: b, opponent: o } =>
+            format!("zwingt {}, das Brett {} zu verteidigen", player(o, l), board(b, l)),
+        Explanation::ForcesIntoFork { board: b, opponent: o } =>
+            format!("zwingt {} auf das Brett {}, wo du eine Gabel hast", player(o, l), board(b, l)),
+        Explanation::Positional => "bester Positionszug".into(),
+    }
+}
+
+pub fn render_one(explanation: &Explanation, locale: Locale) -> String {
+    match locale {
+        Locale::En => render_en(explanation),
+        Locale::Es => render_es(explanation),
+        Locale::De => render_de(explanation),
+    }
+}
+
+pub fn render(explanations: &[Explanation], locale: Locale) -> String {
+    let text = explanations.iter()
+        .map(|e| render_one(e, locale))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => text,
+    }
+}
+
+// Lesson text by the codes in lessons.json.
+const LESSONS_EN: &[(&str, &str)] = &[
+    ("sending.title", "Where your opponent plays next"),
+    ("sending.1", "Blue moves first and may play anywhere. Play the top-right square of the center board."),
+    ("sending.1.success", "The square you pick decides where Red plays next: Red must now play in the top-right board."),
+    ("sending.1.retry", "Look for the top-right square of the big center board."),
+    ("sending.2", "Red answered in the center square of the top-right board, so you must play in the center board. Send Red to the bottom-left board."),
+    ("sending.2.success", "Red now has to play in the bottom-left board."),
+    ("sending.2.retry", "The bottom-left square of the center board sends Red to the bottom-left board."),
+    ("sending.3", "Red has two in a row on the top-left board. Play in the center board without sending Red there."),
+    ("sending.3.success", "Good: Red can't reach the top-left board this turn."),
+    ("sending.3.retry", "The top-left square sends Red to the top-left board, where Red can win it right away."),
+    ("decided-boards.title", "Won and full boards"),
+    ("decided-boards.1", "Red has won the top-left board, but it still has empty squares, so you must play there. The square still decides where Red goes: send Red to the bottom-right board."),
+    ("decided-boards.1.success", "A won board can't change hands, but its squares still steer your opponent."),
+    ("decided-boards.1.retry", "The bottom-right square sends Red to the bottom-right board."),
+    ("decided-boards.2", "You were sent to the top board, which is full with no winner. A full board counts for neither player, and being sent there lets you play on any open board. Take the center square of the center board."),
+    ("decided-boards.2.success", "A free choice of board is a big advantage, so avoid handing one to your opponent."),
+    ("decided-boards.2.retry", "Play the middle square of the middle board."),
+    ("forks.title", "Forks"),
+    ("forks.1", "Make two threats at once: find a square in the center board that lines up with both of your corners."),
+    ("forks.1.success", "That's a fork: Red can only block one of your two threats."),
+    ("forks.1.retry", "Look for a square that leaves two rows each one mark short."),
+    ("forks.2", "Red blocked one threat, but the other is still open. Win the center board."),
+    ("forks.2.success", "The fork paid off: the center board is yours."),
+    ("forks.2.retry", "Complete the right-hand column of the center board."),
+    ("meta-threats.title", "Winning the big board"),
+    ("meta-threats.1", "You own the top-left and top boards. Win the top-right board to complete the top row."),
+    ("meta-threats.1.success", "Three boards in a row wins the game!"),
+    ("meta-threats.1.retry", "Finish the top row of the top-right board."),
+    ("meta-threats.2", "With the top-left board already yours, winning the center board threatens the whole diagonal. Take it."),
+    ("meta-threats.2.success", "Now Red has to stop you winning the bottom-right board as well as everything else."),
+    ("meta-threats.2.retry", "Complete the top row of the center board."),
+    ("meta-threats.3", "Red owns the center and bottom-right boards and threatens the top-left board. Block Red's win there."),
+    ("meta-threats.3.success", "Blocked: Red's diagonal is still one board short."),
+    ("meta-threats.3.retry", "Red wins the top-left board with its top-right square. Take it first."),
+];
+
+const LESSONS_ES: &[(&str, &str)] = &[
+    ("sending.title", "Dónde juega tu rival"),
+    ("sending.1", "Azul mueve primero y puede jugar en cualquier sitio. Juega la casilla superior derecha del tablero central."),
+    ("sending.1.success", "La casilla que eliges decide dónde juega Rojo: ahora Rojo tiene que jugar en el tablero superior derecho."),
+    ("sending.1.retry", "Busca la casilla superior derecha del gran tablero central."),
+    ("sending.2", "Rojo respondió en la casilla central del tablero superior derecho, así que tienes que jugar en el tablero central. Envía a Rojo al tablero inferior izquierdo."),
+    ("sending.2.success", "Ahora Rojo tiene que jugar en el tablero inferior izquierdo."),
+    ("sending.2.retry", "La casilla inferior izquierda del tablero central envía a Rojo al tablero inferior izquierdo."),
+    ("sending.3", "Rojo tiene dos en línea en el tablero superior izquierdo. Juega en el tablero central sin enviar allí a Rojo."),
+    ("sending.3.success", "Bien: Rojo no puede llegar al tablero superior izquierdo en este turno."),
+    ("sending.3.retry", "La casilla superior izquierda envía a Rojo al tablero superior izquierdo, donde puede ganarlo enseguida."),
+    ("decided-boards.title", "Tableros ganados y llenos"),
+    ("decided-boards.1", "Rojo ha ganado el tablero superior izquierdo, pero aún tiene casillas libres, así que tienes que jugar allí. La casilla sigue decidiendo adónde va Rojo: envía a Rojo al tablero inferior derecho."),
+    ("decided-boards.1.success", "Un tablero ganado no puede cambiar de dueño, pero sus casillas siguen guiando a tu rival."),
+    ("decided-boards.1.retry", "La casilla inferior derecha envía a Rojo al tablero inferior derecho."),
+    ("decided-boards.2", "Te enviaron al tablero superior, que está lleno y sin ganador. Un tablero lleno no cuenta para nadie, y si te envían allí puedes jugar en cualquier tablero con casillas libres. Toma la casilla central del tablero central."),
+    ("decided-boards.2.success", "Elegir tablero libremente es una gran ventaja, así que evita dársela a tu rival."),
+    ("decided-boards.2.retry", "Juega la casilla del medio del tablero del medio."),
+    ("forks.title", "Dobles amenazas"),
+    ("forks.1", "Crea dos amenazas a la vez: busca una casilla del tablero central que se alinee con tus dos esquinas."),
+    ("forks.1.success", "Eso es una doble amenaza: Rojo solo puede bloquear una de las dos."),
+    ("forks.1.retry", "Busca una casilla que deje dos líneas a una marca de completarse."),
+    ("forks.2", "Rojo bloqueó una amenaza, pero la otra sigue abierta. Gana el tablero central."),
+    ("forks.2.success", "La doble amenaza dio resultado: el tablero central es tuyo."),
+    ("forks.2.retry", "Completa la columna derecha del tablero central."),
+    ("meta-threats.title", "Ganar el tablero grande"),
+    ("meta-threats.1", "Tienes los tableros superior izquierdo y superior. Gana el tablero superior derecho para completar la fila de arriba."),
+    ("meta-threats.1.success", "¡Tres tableros en línea ganan la partida!"),
+    ("meta-threats.1.retry", "Completa la fila de arriba del tablero superior derecho."),
+    ("meta-threats.2", "Con el tablero superior izquierdo ya tuyo, ganar el tablero central amenaza toda la diagonal. Gánalo."),
+    ("meta-threats.2.success", "Ahora Rojo tiene que impedir que ganes el tablero inferior derecho, además de todo lo demás."),
+    ("meta-threats.2.retry", "Completa la fila de arriba del tablero central."),
+    ("meta-threats.3", "Rojo tiene los tableros central e inferior derecho y amenaza el tablero superior izquierdo. Bloquea allí la victoria de Rojo."),
+    ("meta-threats.3.success", "Bloqueado: a la diagonal de Rojo todavía le falta un tablero."),
+    ("meta-threats.3.retry", "Rojo gana el tablero superior izquierdo con su casilla superior derecha. Tómala tú primero."),
+];
+
+const LESSONS_DE: &[(&str, &str)] = &[
+    ("sending.title", "Wo dein Gegner als Nächstes spielt"),
+    ("sending.1", "Blau beginnt und darf überall spielen. Spiele das Feld oben rechts im Brett in der Mitte."),
+    ("sending.1.success", "Das Feld, das du wählst, bestimmt, wo Rot als Nächstes spielt: Rot muss jetzt im Brett oben rechts spielen."),
+    ("sending.1.retry", "Suche das Feld oben rechts im großen Brett in der Mitte."),
+    ("sending.2", "Rot hat im mittleren Feld des Bretts oben rechts geantwortet, also musst du im Brett in der Mitte spielen. Schicke Rot auf das Brett unten links."),
+    ("sending.2.success", "Rot muss jetzt im Brett unten links spielen."),
+    ("sending.2.retry", "Das Feld unten links im Brett in der Mitte schickt Rot auf das Brett unten links."),
+    ("sending.3", "Rot hat zwei in einer Reihe auf dem Brett oben links. Spiele im Brett in der Mitte, ohne Rot dorthin zu schicken."),
+    ("sending.3.success", "Gut: Rot kommt in diesem Zug nicht an das Brett oben links."),
+    ("sending.3.retry", "Das Feld oben links schickt Rot auf das Brett oben links, wo Rot es sofort gewinnen kann."),
+    ("decided-boards.title", "Gewonnene und volle Bretter"),
+    ("decided-boards.1", "Rot hat das Brett oben links gewonnen, aber es hat noch freie Felder, also musst du dort spielen. Das Feld bestimmt trotzdem, wohin Rot geht: Schicke Rot auf das Brett unten rechts."),
+    ("decided-boards.1.success", "Ein gewonnenes Brett wechselt nicht mehr den Besitzer, aber seine Felder lenken weiter deinen Gegner."),
+    ("decided-boards.1.retry", "Das Feld unten rechts schickt Rot auf das Brett unten rechts."),
+    ("decided-boards.2", "Du wurdest auf das Brett oben geschickt, das voll ist und keinen Sieger hat. Ein volles Brett zählt für niemanden, und wer dorthin geschickt wird, darf auf jedem Brett mit freien Feldern spielen. Nimm das mittlere Feld im Brett in der Mitte."),
+    ("decided-boards.2.success", "Das Brett frei wählen zu dürfen ist ein großer Vorteil, also überlass das nicht deinem Gegner."),
+    ("decided-boards.2.retry", "Spiele das mittlere Feld des mittleren Bretts."),
+    ("forks.title", "Gabeln"),
+    ("forks.1", "Mach zwei Drohungen auf einmal: Finde ein Feld im Brett in der Mitte, das mit deinen beiden Ecken eine Linie bildet."),
+    ("forks.1.success", "Das ist eine Gabel: Rot kann nur eine deiner beiden Drohungen blockieren."),
+    ("forks.1.retry", "Suche ein Feld, nach dem zwei Reihen jeweils nur noch ein Zeichen fehlt."),
+    ("forks.2", "Rot hat eine Drohung blockiert, aber die andere ist noch offen. Gewinne das Brett in der Mitte."),
+    ("forks.2.success", "Die Gabel hat sich gelohnt: Das Brett in der Mitte gehört dir."),
+    ("forks.2.retry", "Vervollständige die rechte Spalte des Bretts in der Mitte."),
+    ("meta-threats.title", "Das große Brett gewinnen"),
+    ("meta-threats.1", "Dir gehören die Bretter oben links und oben. Gewinne das Brett oben rechts, um die obere Reihe zu vervollständigen."),
+    ("meta-threats.1.success", "Drei Bretter in einer Reihe gewinnen das Spiel!"),
+    ("meta-threats.1.retry", "Vervollständige die obere Reihe des Bretts oben rechts."),
+    ("meta-threats.2", "Das Brett oben links gehört schon dir, also droht ein Sieg im Brett in der Mitte die ganze Diagonale. Hol es dir."),
+    ("meta-threats.2.success", "Jetzt muss Rot zusätzlich verhindern, dass du das Brett unten rechts gewinnst."),
+    ("meta-threats.2.retry", "Vervollständige die obere Reihe des Bretts in der Mitte."),
+    ("meta-threats.3", "Rot gehören die Bretter in der Mitte und unten rechts, und Rot droht, das Brett oben links zu gewinnen. Blockiere Rot dort."),
+    ("meta-threats.3.success", "Blockiert: Rots Diagonale fehlt weiterhin ein Brett."),
+    ("meta-threats.3.retry", "Rot gewinnt das Brett oben links mit dem Feld oben rechts. Nimm es zuerst."),
+];
+
+// Falls back to English, then to the code itself, so a missing translation still shows something.
+pub fn lesson_text(code: &str, locale: Locale) -> String {
+    let table = match locale {
+        Locale::En => LESSONS_EN,
+        Locale::Es => LESSONS_ES,
+        Locale::De => LESSONS_DE,
+    };
+    let find = |table: &[(&str, &'static str)]| table.iter().find(|(c, _)| *c == code).map(|(_, text)| *text);
+    find(table).or_else(|| find(LESSONS_EN)).unwrap_or(code).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_lesson_text_is_translated() {
+        let codes = |table: &[(&'static str, &str)]| table.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+        assert_eq!(codes(LESSONS_ES), codes(LESSONS_EN));
+        assert_eq!(codes(LESSONS_DE), codes(LESSONS_EN));
+    }
+}
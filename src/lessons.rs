@@ -0,0 +1,156 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{generate_explanation, legal_moves, notation, side_to_move, Explanation, GameState};
+
+const LESSONS: &str = include_str!("lessons.json");
+
+static LESSON_SET: OnceLock<Vec<Lesson>> = OnceLock::new();
+
+// Text fields hold message codes, rendered for the learner's language with `i18n::lesson_text`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LessonStep {
+    pub position: String,
+    pub message: String,
+    // Moves the learner may try; empty means any legal move.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed: Vec<(usize, usize)>,
+    // Moves that complete the step; empty means any allowed move.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expected: Vec<(usize, usize)>,
+    #[serde(default)]
+    pub success: String,
+    #[serde(default)]
+    pub retry: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lesson {
+    pub id: String,
+    pub title: String,
+    pub steps: Vec<LessonStep>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LessonSession {
+    pub lesson_id: String,
+    pub step: usize,
+    pub state: GameState,
+    pub prompt: String,
+    pub completed: bool,
+}
+
+#[derive(Serialize)]
+pub struct LessonFeedback {
+    pub ok: bool,
+    pub correct: bool,
+    pub session: LessonSession,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub explanation: Vec<Explanation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn lessons() -> &'static [Lesson] {
+    LESSON_SET.get_or_init(|| serde_json::from_str(LESSONS).expect("built-in lessons.json is valid"))
+}
+
+pub fn lesson(id: &str) -> Option<&'static Lesson> {
+    lessons().iter().find(|l| l.id == id)
+}
+
+impl LessonSession {
+    pub fn new(lesson: &Lesson) -> Result<Self, String> {
+        let first = lesson.steps.first().ok_or("Lesson has no steps")?;
+        Ok(Self {
+            lesson_id: lesson.id.clone(),
+            step: 0,
+            state: notation::decode(&first.position)?,
+            prompt: first.message.clone(),
+            completed: false,
+        })
+    }
+
+    pub fn attempt(&self, board_idx: usize, cell_idx: usize) -> LessonFeedback {
+        let reject = |msg: &str| LessonFeedback {
+            ok: false,
+            correct: false,
+            session: self.clone(),
+            message: None,
+            explanation: Vec::new(),
+            error: Some(msg.into()),
+        };
+        let step = match lesson(&self.lesson_id).and_then(|l| l.steps.get(self.step)) {
+            Some(s) if !self.completed => s,
+            _ => return reject("Lesson is already finished"),
+        };
+        let player = match side_to_move(&self.state) {
+            Some(p) => p,
+            None => return reject("Game is over"),
+        };
+        let m = (board_idx, cell_idx);
+        if !legal_moves(&self.state).contains(&m) {
+            return reject("That square isn't playable right now");
+        }
+        if !step.allowed.is_empty() && !step.allowed.contains(&m) {
+            return reject("Try a different square for this step");
+        }
+
+        let explanation = generate_explanation(&self.state, board_idx, cell_idx, player);
+        let correct = step.expected.is_empty() || step.expected.contains(&m);
+        let mut session = self.clone();
+        let message = if correct {
+            session.advance();
+            &step.success
+        } else {
+            &step.retry
+        };
+        LessonFeedback {
+            ok: true,
+            correct,
+            session,
+            message: Some(message.clone()).filter(|m| !m.is_empty()),
+            explanation,
+            error: None,
+        }
+    }
+
+    fn advance(&mut self) {
+        let next = lesson(&self.lesson_id).and_then(|l| l.steps.get(self.step + 1));
+        match next.map(|s| (s, notation::decode(&s.position))) {
+            Some((s, Ok(state))) => {
+                self.step += 1;
+                self.state = state;
+                self.prompt = s.message.clone();
+            }
+            _ => self.completed = true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{lesson_text, Locale};
+
+    #[test]
+    fn every_step_is_a_reachable_position() {
+        for lesson in lessons() {
+            assert_ne!(lesson_text(&lesson.title, Locale::En), lesson.title);
+            for (i, step) in lesson.steps.iter().enumerate() {
+                let state = notation::decode(&step.position)
+                    .unwrap_or_else(|e| panic!("{} step {}: {}", lesson.id, i + 1, e));
+                let legal = legal_moves(&state);
+                for code in [&step.message, &step.success, &step.retry] {
+                    assert_ne!(lesson_text(code, Locale::En), *code, "{} step {}: no text", lesson.id, i + 1);
+                }
+                for m in step.allowed.iter().chain(&step.expected) {
+                    assert!(legal.contains(m), "{} step {}: {:?} isn't legal", lesson.id, i + 1, m);
+                }
+            }
+        }
+    }
+}
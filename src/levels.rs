@@ -0,0 +1,332 @@
+use std::sync::{Arc, OnceLock, RwLock};
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{self, OpponentModel};
+use crate::{
+    creates_fork, creates_meta_threat, is_dead, opponent, pick_random, score_moves_with,
+    would_win_board, would_win_meta, Cell, EvalWeights, GameState, WIN_LINES,
+};
+
+const DEFAULT_LEVELS: &str = include_str!("levels.json");
+const PERSONALITIES: &str = include_str!("personalities.json");
+
+static LEVELS: RwLock<Option<Arc<Vec<LevelDef>>>> = RwLock::new(None);
+static PERSONALITY_DEFS: OnceLock<Vec<Personality>> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Heuristic {
+    AvoidBoardWin,
+    WinBoard,
+    WinGame,
+    WinContested,
+    WinMetaThreat,
+    Block,
+    BlockGame,
+    BlockFork,
+    Fork,
+    SafeSendBalanced,
+    SafeSend,
+    SafeSendFork,
+    ProtectMetaLine,
+    SafeSendMeta,
+    SendTrap,
+    SendEmpty,
+    SendMostEmpty,
+    SendDead,
+    SendFreeChoice,
+    PreferCenter,
+    PreferCorners,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rule {
+    pub heuristic: Heuristic,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub narrow: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefer: Vec<Heuristic>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SearchSettings {
+    pub depth: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LevelDef {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f64>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<SearchSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<EvalWeights>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Personality {
+    pub id: String,
+    #[serde(flatten)]
+    pub def: LevelDef,
+}
+
+fn decided(state: &GameState, board_idx: usize) -> bool {
+    state.board_full[board_idx] || state.board_winners[board_idx] != Cell::Empty
+}
+
+fn has_winning_cell(cells: &[Cell; 9], player: Cell) -> bool {
+    cells.iter().enumerate().any(|(i, &cell)| cell == Cell::Empty && would_win_board(cells, i, player))
+}
+
+fn has_fork_cell(cells: &[Cell; 9], player: Cell) -> bool {
+    cells.iter().enumerate().any(|(i, &cell)| cell == Cell::Empty && creates_fork(cells, i, player))
+}
+
+fn tiered(moves: &[(usize, usize)], squares: &[usize]) -> Vec<(usize, usize)> {
+    let cell: Vec<_> = moves.iter().filter(|&&(_, c)| squares.contains(&c)).copied().collect();
+    if !cell.is_empty() {
+        let both: Vec<_> = cell.iter().filter(|&&(b, _)| squares.contains(&b)).copied().collect();
+        return if both.is_empty() { cell } else { both };
+    }
+    moves.iter().filter(|&&(b, _)| squares.contains(&b)).copied().collect()
+}
+
+impl Heuristic {
+    fn matches(self, state: &GameState, me: Cell, (b, c): (usize, usize)) -> bool {
+        let opp = opponent(me);
+        let open = state.board_winners[b] == Cell::Empty;
+        match self {
+            Heuristic::AvoidBoardWin => !would_win_board(&state.cells[b], c, me),
+            Heuristic::WinBoard => open && would_win_board(&state.cells[b], c, me),
+            Heuristic::WinGame => {
+                open && would_win_board(&state.cells[b], c, me)
+                    && would_win_meta(&state.board_winners, b, me)
+            }
+            Heuristic::WinContested => {
+                open && would_win_board(&state.cells[b], c, me)
+                    && has_winning_cell(&state.cells[b], opp)
+            }
+            Heuristic::WinMetaThreat => {
+                open && would_win_board(&state.cells[b], c, me)
+                    && creates_meta_threat(&state.board_winners, b, me)
+            }
+            Heuristic::Block => open && would_win_board(&state.cells[b], c, opp),
+            Heuristic::BlockGame => {
+                open && would_win_board(&state.cells[b], c, opp)
+                    && would_win_meta(&state.board_winners, b, opp)
+            }
+            Heuristic::BlockFork => open && creates_fork(&state.cells[b], c, opp),
+            Heuristic::Fork => open && creates_fork(&state.cells[b], c, me),
+            Heuristic::SafeSendBalanced => {
+                decided(state, c) || {
+                    let theirs = state.cells[c].iter().filter(|&&cell| cell == opp).count();
+                    let mine = state.cells[c].iter().filter(|&&cell| cell == me).count();
+                    theirs <= mine
+                }
+            }
+            Heuristic::SafeSend => decided(state, c) || !has_winning_cell(&state.cells[c], opp),
+            Heuristic::SafeSendFork => decided(state, c) || !has_fork_cell(&state.cells[c], opp),
+            Heuristic::ProtectMetaLine => {
+                decided(state, c) || !WIN_LINES.iter().any(|line| {
+                    line.contains(&c)
+                        && line.iter().filter(|&&i| state.board_winners[i] == me).count() == 2
+                })
+            }
+            Heuristic::SafeSendMeta => {
+                decided(state, c)
+                    || (!would_win_meta(&state.board_winners, c, opp)
+                        && !creates_meta_threat(&state.board_winners, c, opp))
+            }
+            Heuristic::SendTrap => {
+                if state.board_full[c] { return false; }
+                let empties: Vec<usize> = state.cells[c].iter().enumerate()
+                    .filter(|(_, &cell)| cell == Cell::Empty)
+                    .map(|(i, _)| i)
+                    .collect();
+                empties.len() == 1 && has_winning_cell(&state.cells[empties[0]], me)
+            }
+            Heuristic::SendEmpty => {
+                !state.board_full[c] && state.cells[c].iter().all(|&cell| cell == Cell::Empty)
+            }
+            Heuristic::SendDead => decided(state, c) || is_dead(&state.cells[c]),
+            Heuristic::SendFreeChoice => {
+                state.board_full[c]
+                    || (b == c && state.cells[c].iter().filter(|&&cell| cell == Cell::Empty).count() == 1)
+            }
+            Heuristic::SendMostEmpty | Heuristic::PreferCenter | Heuristic::PreferCorners => true,
+        }
+    }
+
+    pub fn apply(self, state: &GameState, me: Cell, moves: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        match self {
+            Heuristic::SendMostEmpty => {
+                let empty_count = |c: usize| {
+                    if state.board_full[c] { 0 }
+                    else { state.cells[c].iter().filter(|&&cell| cell == Cell::Empty).count() }
+                };
+                let max_empty = match moves.iter().map(|&(_, c)| empty_count(c)).max() {
+                    Some(m) => m,
+                    None => return Vec::new(),
+                };
+                moves.iter().filter(|&&(_, c)| empty_count(c) == max_empty).copied().collect()
+            }
+            Heuristic::PreferCenter => tiered(moves, &[4]),
+            Heuristic::PreferCorners => tiered(moves, &[0, 2, 6, 8]),
+            _ => moves.iter().filter(|&&m| self.matches(state, me, m)).copied().collect(),
+        }
+    }
+}
+
+impl LevelDef {
+    pub fn choose(&self, state: &GameState, me: Cell, moves: &[(usize, usize)]) -> (usize, usize) {
+        self.choose_with_model(state, me, moves, None)
+    }
+
+    pub fn choose_with_model(
+        &self,
+        state: &GameState,
+        me: Cell,
+        moves: &[(usize, usize)],
+        model: Option<&OpponentModel>,
+    ) -> (usize, usize) {
+        let (candidates, decided) = self.shortlist(state, me, moves);
+        if decided { return pick_random(&candidates); }
+
+        match self.search {
+            Some(search) => {
+                let weights = self.weights.unwrap_or_default();
+                let scored = match model {
+                    Some(m) => model::score_moves_modeled(state, &candidates, search.depth, &weights, m),
+                    None => score_moves_with(state, &candidates, search.depth, &weights),
+                };
+                let best = scored.iter().map(|&(_, s)| s).max().unwrap();
+                let best_moves: Vec<_> = scored.iter()
+                    .filter(|&&(_, s)| s == best)
+                    .map(|&(m, _)| m)
+                    .collect();
+                *best_moves.choose(&mut rand::thread_rng()).unwrap()
+            }
+            None => pick_random(&candidates),
+        }
+    }
+
+    // The moves left to pick from once the rules have had their say, and whether a rule settled on them;
+    // if not, they're what the narrowing rules let through.
+    fn shortlist(&self, state: &GameState, me: Cell, moves: &[(usize, usize)]) -> (Vec<(usize, usize)>, bool) {
+        let mut candidates = moves.to_vec();
+        for rule in &self.rules {
+            let matched = rule.heuristic.apply(state, me, &candidates);
+            if matched.is_empty() { continue; }
+            if rule.narrow {
+                candidates = matched;
+                continue;
+            }
+            for pref in &rule.prefer {
+                let preferred = pref.apply(state, me, &matched);
+                if !preferred.is_empty() { return (preferred, true); }
+            }
+            return (matched, true);
+        }
+        (candidates, false)
+    }
+
+    // Ranks moves by the rules alone: the moves this level would pick from score 0, the ones it would
+    // pick from once those were gone -1, and so on.
+    pub fn rank(&self, state: &GameState, me: Cell, moves: &[(usize, usize)]) -> Vec<((usize, usize), i32)> {
+        let mut left = moves.to_vec();
+        let mut ranked = Vec::with_capacity(moves.len());
+        let mut score = 0;
+        while !left.is_empty() {
+            let (best, _) = self.shortlist(state, me, &left);
+            left.retain(|m| !best.contains(m));
+            ranked.extend(best.into_iter().map(|m| (m, score)));
+            score -= 1;
+        }
+        ranked
+    }
+}
+
+pub fn parse_levels(json: &str) -> Result<Vec<LevelDef>, String> {
+    let levels: Vec<LevelDef> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    if levels.is_empty() {
+        return Err("At least one level is required".into());
+    }
+    Ok(levels)
+}
+
+pub fn levels() -> Arc<Vec<LevelDef>> {
+    if let Some(levels) = LEVELS.read().unwrap().as_ref() {
+        return levels.clone();
+    }
+    let mut slot = LEVELS.write().unwrap();
+    slot.get_or_insert_with(|| Arc::new(parse_levels(DEFAULT_LEVELS).expect("built-in levels.json is valid")))
+        .clone()
+}
+
+pub fn set_levels(levels: Vec<LevelDef>) {
+    *LEVELS.write().unwrap() = Some(Arc::new(levels));
+}
+
+pub fn max_level() -> u32 {
+    levels().len() as u32 - 1
+}
+
+pub fn level_def(level: u32) -> LevelDef {
+    let levels = levels();
+    levels[(level as usize).min(levels.len() - 1)].clone()
+}
+
+// The strongest level that plays by its rules without searching, which is what beginner hints follow.
+pub fn heuristic_level() -> LevelDef {
+    levels().iter()
+        .filter(|d| d.search.is_none())
+        .max_by(|a, b| a.rating.unwrap_or(0.0).total_cmp(&b.rating.unwrap_or(0.0)))
+        .cloned()
+        .unwrap_or_else(|| level_def(0))
+}
+
+pub fn personalities() -> &'static [Personality] {
+    PERSONALITY_DEFS.get_or_init(|| {
+        serde_json::from_str(PERSONALITIES).expect("built-in personalities.json is valid")
+    })
+}
+
+pub fn personality(id: &str) -> Option<&'static Personality> {
+    personalities().iter().find(|p| p.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apply_move, legal_moves, ranked_hints, side_to_move, HintStrength};
+
+    #[test]
+    fn beginner_hints_follow_the_level_rules() {
+        let mut state = GameState::new();
+        for (b, c) in [(4, 0), (0, 4), (4, 1), (1, 4)] {
+            let player = side_to_move(&state).unwrap();
+            apply_move(&mut state, b, c, player);
+        }
+        let hints = ranked_hints(&state, 9, HintStrength::Beginner);
+        assert_eq!(hints.len(), legal_moves(&state).len());
+        assert_eq!((hints[0].board_idx, hints[0].cell_idx, hints[0].score), (4, 2, 0));
+        assert!(hints[1..].iter().all(|h| h.score < 0));
+    }
+
+    #[test]
+    fn ranking_puts_every_move_in_order() {
+        let state = GameState::new();
+        let moves = legal_moves(&state);
+        let ranked = heuristic_level().rank(&state, side_to_move(&state).unwrap(), &moves);
+        assert_eq!(ranked.len(), moves.len());
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(heuristic_level().search.is_none());
+    }
+}
@@ -4,6 +4,17 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+pub mod i18n;
+pub mod lessons;
+pub mod levels;
+pub mod model;
+pub mod notation;
+pub mod progress;
+pub mod puzzle;
+pub mod rating;
+#[cfg(feature = "server")]
+pub mod server;
+
 pub const WIN_LINES: [[usize; 3]; 8] = [
     [0, 1, 2],
     [3, 4, 5],
@@ -42,8 +53,66 @@ pub struct GameState {
     pub status: GameStatus,
     pub last_blue: Option<(usize, usize)>,
     pub last_red: Option<(usize, usize)>,
+    #[serde(default)]
+    pub ply: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handicap: Option<Handicap>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Handicap {
+    PreWonBoard { player: Cell, board: usize },
+    ExtraMove { player: Cell },
+    FreeFirstMove { player: Cell },
 }
 
+impl Handicap {
+    pub fn player(&self) -> Cell {
+        match *self {
+            Handicap::PreWonBoard { player, .. }
+            | Handicap::ExtraMove { player }
+            | Handicap::FreeFirstMove { player } => player,
+        }
+    }
+
+    // Approximate worth of the handicap in rating points.
+    pub fn rating_value(&self) -> f64 {
+        match *self {
+            Handicap::PreWonBoard { board: 4, .. } => 250.0,
+            Handicap::PreWonBoard { .. } => 200.0,
+            Handicap::ExtraMove { .. } => 100.0,
+            Handicap::FreeFirstMove { .. } => 50.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), HandicapError> {
+        if self.player() == Cell::Empty { return Err(HandicapError::InvalidPlayer); }
+        if let Handicap::PreWonBoard { board, .. } = *self {
+            if board >= 9 { return Err(HandicapError::InvalidBoard); }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HandicapError {
+    InvalidPlayer,
+    InvalidBoard,
+}
+
+impl std::fmt::Display for HandicapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HandicapError::InvalidPlayer => "Handicap player must be blue or red",
+            HandicapError::InvalidBoard => "Handicap board must be 0 to 8",
+        })
+    }
+}
+
+impl std::error::Error for HandicapError {}
+
 impl GameState {
     pub fn new() -> Self {
         Self {
@@ -54,7 +123,25 @@ impl GameState {
             status: GameStatus::BlueToMove,
             last_blue: None,
             last_red: None,
+            ply: 0,
+            handicap: None,
+        }
+    }
+
+    pub fn with_handicap(handicap: Handicap) -> Result<Self, HandicapError> {
+        handicap.validate()?;
+        let mut state = Self::new();
+        if let Handicap::PreWonBoard { player, board } = handicap {
+            state.board_winners[board] = player;
         }
+        state.handicap = Some(handicap);
+        Ok(state)
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -64,13 +151,45 @@ pub struct MoveResponse {
     pub state: GameState,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<model::OpponentModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<Explanation>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WinProbability {
+    pub blue: f64,
+    pub draw: f64,
+    pub red: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum Explanation {
+    WinsGame { board: usize },
+    WinsBoardThreatensGame { board: usize },
+    WinsBoard { board: usize },
+    BlocksBoard { board: usize, opponent: Cell },
+    ThreatensGame { board: usize },
+    CreatesFork { board: usize },
+    GivesFreeChoice { opponent: Cell },
+    SendsToDecidedBoard { board: usize, opponent: Cell },
+    SendsToGameWin { board: usize, opponent: Cell },
+    SendsToBoardWin { board: usize, opponent: Cell },
+    SendsToFork { board: usize, opponent: Cell },
+    ForcesDefense { board: usize, opponent: Cell },
+    ForcesIntoFork { board: usize, opponent: Cell },
+    Positional,
 }
 
 #[derive(Serialize)]
 pub struct HintResponse {
     pub board_idx: usize,
     pub cell_idx: usize,
-    pub explanation: String,
+    pub explanation: Vec<Explanation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 pub fn check_winner(cells: &[Cell; 9]) -> Cell {
@@ -87,7 +206,7 @@ pub fn is_board_full(cells: &[Cell; 9]) -> bool {
     cells.iter().all(|c| *c != Cell::Empty)
 }
 
-fn is_meta_dead(board_winners: &[Cell; 9]) -> bool {
+pub fn is_dead(board_winners: &[Cell; 9]) -> bool {
     WIN_LINES.iter().all(|line| {
         let has_blue = line.iter().any(|&i| board_winners[i] == Cell::Blue);
         let has_red = line.iter().any(|&i| board_winners[i] == Cell::Red);
@@ -123,7 +242,7 @@ pub fn apply_move(state: &mut GameState, board_idx: usize, cell_idx: usize, play
         state.status = GameStatus::BlueWins;
     } else if meta_winner == Cell::Red {
         state.status = GameStatus::RedWins;
-    } else if is_meta_dead(&state.board_winners)
+    } else if is_dead(&state.board_winners)
         || state.board_winners.iter().zip(state.board_full.iter()).all(|(w, f)| *w != Cell::Empty || *f) {
         state.status = GameStatus::Draw;
     } else {
@@ -133,6 +252,35 @@ pub fn apply_move(state: &mut GameState, board_idx: usize, cell_idx: usize, play
             _ => state.status,
         };
     }
+
+    state.ply += 1;
+    if let Some(handicap) = state.handicap {
+        apply_handicap(state, handicap, player);
+    }
+}
+
+// Blue always opens, so Blue's first move is ply 0 and Red's is ply 1.
+fn first_ply(player: Cell) -> u32 {
+    if player == Cell::Blue { 0 } else { 1 }
+}
+
+// Blue's opening move is already unrestricted, so its first forced board comes a turn later.
+fn first_forced_ply(player: Cell) -> u32 {
+    if player == Cell::Blue { 2 } else { 1 }
+}
+
+fn apply_handicap(state: &mut GameState, handicap: Handicap, mover: Cell) {
+    let next = opponent(mover);
+    if side_to_move(state) != Some(next) { return; }
+    match handicap {
+        Handicap::ExtraMove { player } if player == mover && state.ply == first_ply(player) + 1 => {
+            state.status = if player == Cell::Blue { GameStatus::BlueToMove } else { GameStatus::RedToMove };
+        }
+        Handicap::FreeFirstMove { player } if player == next && state.ply == first_forced_ply(player) => {
+            state.required_board = None;
+        }
+        _ => {}
+    }
 }
 
 pub fn legal_moves(state: &GameState) -> Vec<(usize, usize)> {
@@ -153,19 +301,70 @@ pub fn legal_moves(state: &GameState) -> Vec<(usize, usize)> {
     moves
 }
 
-fn would_win_board(cells: &[Cell; 9], cell_idx: usize, player: Cell) -> bool {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveError {
+    InvalidIndices,
+    GameOver,
+    NotYourTurn,
+    IllegalMove,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MoveError::InvalidIndices => "Invalid indices",
+            MoveError::GameOver => "Game is already over",
+            MoveError::NotYourTurn => "Not your turn",
+            MoveError::IllegalMove => "Illegal move",
+        })
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+pub fn check_move(state: &GameState, player: Cell, board_idx: usize, cell_idx: usize) -> Result<(), MoveError> {
+    if board_idx >= 9 || cell_idx >= 9 {
+        return Err(MoveError::InvalidIndices);
+    }
+    match side_to_move(state) {
+        None => return Err(MoveError::GameOver),
+        Some(p) if p != player => return Err(MoveError::NotYourTurn),
+        _ => {}
+    }
+    if !legal_moves(state).contains(&(board_idx, cell_idx)) {
+        return Err(MoveError::IllegalMove);
+    }
+    Ok(())
+}
+
+// The checked way in for anything outside the crate: validates the move, then applies it.
+pub fn play_move(state: &mut GameState, player: Cell, board_idx: usize, cell_idx: usize) -> Result<(), MoveError> {
+    check_move(state, player, board_idx, cell_idx)?;
+    apply_move(state, board_idx, cell_idx, player);
+    Ok(())
+}
+
+// Plays for whichever side is to move, with no computer reply, as in pass-and-play.
+pub fn play_turn(state: &mut GameState, board_idx: usize, cell_idx: usize) -> Result<Cell, MoveError> {
+    let player = side_to_move(state).ok_or(MoveError::GameOver)?;
+    play_move(state, player, board_idx, cell_idx)?;
+    Ok(player)
+}
+
+pub fn would_win_board(cells: &[Cell; 9], cell_idx: usize, player: Cell) -> bool {
     let mut test = *cells;
     test[cell_idx] = player;
     check_winner(&test) == player
 }
 
-fn would_win_meta(board_winners: &[Cell; 9], board_idx: usize, player: Cell) -> bool {
+pub fn would_win_meta(board_winners: &[Cell; 9], board_idx: usize, player: Cell) -> bool {
     let mut test = *board_winners;
     test[board_idx] = player;
     check_winner(&test) == player
 }
 
-fn creates_meta_threat(board_winners: &[Cell; 9], board_idx: usize, player: Cell) -> bool {
+pub fn creates_meta_threat(board_winners: &[Cell; 9], board_idx: usize, player: Cell) -> bool {
     let mut test = *board_winners;
     test[board_idx] = player;
     for line in &WIN_LINES {
@@ -179,7 +378,7 @@ fn creates_meta_threat(board_winners: &[Cell; 9], board_idx: usize, player: Cell
     false
 }
 
-fn creates_fork(cells: &[Cell; 9], cell_idx: usize, player: Cell) -> bool {
+pub fn creates_fork(cells: &[Cell; 9], cell_idx: usize, player: Cell) -> bool {
     let mut test = *cells;
     test[cell_idx] = player;
     let threats = (0..9).filter(|&i| {
@@ -188,15 +387,41 @@ fn creates_fork(cells: &[Cell; 9], cell_idx: usize, player: Cell) -> bool {
     threats >= 2
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalWeights {
+    pub board: i32,
+    pub line_two: i32,
+    pub line_one: i32,
+    pub center_board: i32,
+    pub center_cell: i32,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self {
+            board: 100,
+            line_two: 50,
+            line_one: 10,
+            center_board: 5,
+            center_cell: 1,
+        }
+    }
+}
+
 fn evaluate(state: &GameState) -> i32 {
+    evaluate_with(state, &EvalWeights::default())
+}
+
+pub fn evaluate_with(state: &GameState, w: &EvalWeights) -> i32 {
     let meta_w = check_winner(&state.board_winners);
     if meta_w == Cell::Red { return 10000; }
     if meta_w == Cell::Blue { return -10000; }
     let mut score = 0i32;
     for b in 0..9 {
         match state.board_winners[b] {
-            Cell::Red => score += 100,
-            Cell::Blue => score -= 100,
+            Cell::Red => score += w.board,
+            Cell::Blue => score -= w.board,
             _ => {}
         }
     }
@@ -204,400 +429,448 @@ fn evaluate(state: &GameState) -> i32 {
         let red = line.iter().filter(|&&i| state.board_winners[i] == Cell::Red).count();
         let blue = line.iter().filter(|&&i| state.board_winners[i] == Cell::Blue).count();
         if blue == 0 {
-            if red == 2 { score += 50; }
-            else if red == 1 { score += 10; }
+            if red == 2 { score += w.line_two; }
+            else if red == 1 { score += w.line_one; }
         }
         if red == 0 {
-            if blue == 2 { score -= 50; }
-            else if blue == 1 { score -= 10; }
+            if blue == 2 { score -= w.line_two; }
+            else if blue == 1 { score -= w.line_one; }
         }
     }
-    if state.board_winners[4] == Cell::Red { score += 5; }
-    else if state.board_winners[4] == Cell::Blue { score -= 5; }
+    if state.board_winners[4] == Cell::Red { score += w.center_board; }
+    else if state.board_winners[4] == Cell::Blue { score -= w.center_board; }
     for b in 0..9 {
         if state.board_winners[b] == Cell::Empty {
-            if state.cells[b][4] == Cell::Red { score += 1; }
-            else if state.cells[b][4] == Cell::Blue { score -= 1; }
+            if state.cells[b][4] == Cell::Red { score += w.center_cell; }
+            else if state.cells[b][4] == Cell::Blue { score -= w.center_cell; }
         }
     }
     score
 }
 
-fn best_move_two_ply(state: &GameState, moves: &[(usize, usize)]) -> (usize, usize) {
-    let mut best_score = i32::MIN;
+pub(crate) fn pick_random(moves: &[(usize, usize)]) -> (usize, usize) {
+    *moves.choose(&mut rand::thread_rng()).unwrap()
+}
+
+pub fn best_move_for_blue(state: &GameState, moves: &[(usize, usize)]) -> (usize, usize) {
+    let mut best_score = i32::MAX;
     let mut best_moves = Vec::new();
     for &(b, c) in moves {
         let mut s1 = state.clone();
-        apply_move(&mut s1, b, c, Cell::Red);
-        let score = if s1.status != GameStatus::BlueToMove {
+        apply_move(&mut s1, b, c, Cell::Blue);
+        let score = if s1.status != GameStatus::RedToMove {
             evaluate(&s1)
         } else {
-            let blue_moves = legal_moves(&s1);
-            blue_moves.iter().map(|&(bb, bc)| {
+            let red_moves = legal_moves(&s1);
+            red_moves.iter().map(|&(rb, rc)| {
                 let mut s2 = s1.clone();
-                apply_move(&mut s2, bb, bc, Cell::Blue);
+                apply_move(&mut s2, rb, rc, Cell::Red);
                 evaluate(&s2)
-            }).min().unwrap_or(evaluate(&s1))
+            }).max().unwrap_or(evaluate(&s1))
         };
-        if score > best_score {
+        if score < best_score {
             best_score = score;
             best_moves = vec![(b, c)];
         } else if score == best_score {
             best_moves.push((b, c));
         }
     }
-    *best_moves.choose(&mut rand::thread_rng()).unwrap()
+    best_moves[0]
+}
+
+pub(crate) fn opponent(player: Cell) -> Cell {
+    match player {
+        Cell::Blue => Cell::Red,
+        Cell::Red => Cell::Blue,
+        Cell::Empty => Cell::Empty,
+    }
 }
 
-fn best_move_one_ply(state: &GameState, moves: &[(usize, usize)]) -> (usize, usize) {
-    let mut best_score = i32::MIN;
-    let mut best_moves = Vec::new();
-    for &(b, c) in moves {
-        let mut s = state.clone();
-        apply_move(&mut s, b, c, Cell::Red);
-        let score = evaluate(&s);
-        if score > best_score {
-            best_score = score;
-            best_moves = vec![(b, c)];
-        } else if score == best_score {
-            best_moves.push((b, c));
-        }
+// --- Threats ---
+
+// Only squares the player could play on their next turn are listed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PlayerThreats {
+    pub board_wins: Vec<(usize, usize)>,
+    pub forks: Vec<(usize, usize)>,
+    pub meta_threats: Vec<usize>,
+    pub dangerous_sends: Vec<(usize, usize)>,
+}
+
+impl PlayerThreats {
+    pub fn wins_in(&self, board_idx: usize) -> usize {
+        self.board_wins.iter().filter(|&&(b, _)| b == board_idx).count()
     }
-    *best_moves.choose(&mut rand::thread_rng()).unwrap()
 }
 
-fn prefer_center(moves: &[(usize, usize)]) -> Option<(usize, usize)> {
-    let center_cell: Vec<_> = moves.iter().filter(|&&(_, c)| c == 4).copied().collect();
-    if !center_cell.is_empty() {
-        let center_both: Vec<_> = center_cell.iter().filter(|&&(b, _)| b == 4).copied().collect();
-        if !center_both.is_empty() {
-            return Some(*center_both.choose(&mut rand::thread_rng()).unwrap());
-        }
-        return Some(*center_cell.choose(&mut rand::thread_rng()).unwrap());
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ThreatReport {
+    pub blue: PlayerThreats,
+    pub red: PlayerThreats,
+}
+
+impl ThreatReport {
+    pub fn of(&self, player: Cell) -> &PlayerThreats {
+        if player == Cell::Red { &self.red } else { &self.blue }
     }
-    let center_board: Vec<_> = moves.iter().filter(|&&(b, _)| b == 4).copied().collect();
-    if !center_board.is_empty() {
-        return Some(*center_board.choose(&mut rand::thread_rng()).unwrap());
+}
+
+// Cells `player` could play next: their legal moves on their own turn, otherwise the squares of every board
+// the side to move can send them to, or of every board if a move can hand them a free choice.
+fn reachable_cells(state: &GameState, player: Cell) -> Vec<(usize, usize)> {
+    let Some(mover) = side_to_move(state) else { return Vec::new() };
+    if mover == player {
+        return legal_moves(state);
     }
-    None
+    let empties = |b: usize| state.cells[b].iter().filter(|&&c| c == Cell::Empty).count();
+    let mut boards = [false; 9];
+    for (b, c) in legal_moves(state) {
+        // The move itself can fill the board it sends to.
+        if state.board_full[c] || (b == c && empties(c) == 1) {
+            boards = [true; 9];
+            break;
+        }
+        boards[c] = true;
+    }
+    (0..9)
+        .filter(|&b| boards[b] && !state.board_full[b])
+        .flat_map(|b| (0..9).filter(move |&c| state.cells[b][c] == Cell::Empty).map(move |c| (b, c)))
+        .collect()
 }
 
-fn prefer_corners(moves: &[(usize, usize)]) -> Option<(usize, usize)> {
-    let corners = [0, 2, 6, 8];
-    let corner_cell: Vec<_> = moves.iter().filter(|&&(_, c)| corners.contains(&c)).copied().collect();
-    if !corner_cell.is_empty() {
-        let corner_both: Vec<_> = corner_cell.iter().filter(|&&(b, _)| corners.contains(&b)).copied().collect();
-        if !corner_both.is_empty() {
-            return Some(*corner_both.choose(&mut rand::thread_rng()).unwrap());
+fn player_threats(state: &GameState, player: Cell) -> PlayerThreats {
+    let opp = opponent(player);
+    let mut t = PlayerThreats::default();
+    for (b, c) in reachable_cells(state, player) {
+        let open = state.board_winners[b] == Cell::Empty;
+        if open && !t.meta_threats.contains(&b) && would_win_meta(&state.board_winners, b, player) {
+            t.meta_threats.push(b);
+        }
+        if open && would_win_board(&state.cells[b], c, player) {
+            t.board_wins.push((b, c));
+        }
+        if open && creates_fork(&state.cells[b], c, player) {
+            t.forks.push((b, c));
+        }
+        let mut after = state.clone();
+        apply_move(&mut after, b, c, player);
+        if side_to_move(&after) == Some(opp)
+            && legal_moves(&after).iter().any(|&(db, dc)| {
+                after.board_winners[db] == Cell::Empty
+                    && would_win_board(&after.cells[db], dc, opp)
+            })
+        {
+            t.dangerous_sends.push((b, c));
         }
-        return Some(*corner_cell.choose(&mut rand::thread_rng()).unwrap());
     }
-    let corner_board: Vec<_> = moves.iter().filter(|&&(b, _)| corners.contains(&b)).copied().collect();
-    if !corner_board.is_empty() {
-        return Some(*corner_board.choose(&mut rand::thread_rng()).unwrap());
+    t
+}
+
+pub fn threats(state: &GameState) -> ThreatReport {
+    ThreatReport {
+        blue: player_threats(state, Cell::Blue),
+        red: player_threats(state, Cell::Red),
     }
-    None
 }
 
-fn pick_random(moves: &[(usize, usize)]) -> (usize, usize) {
-    *moves.choose(&mut rand::thread_rng()).unwrap()
+// Squares of an open board that would win it for `player`.
+fn winning_cells(cells: &[Cell; 9], player: Cell) -> usize {
+    (0..9).filter(|&c| cells[c] == Cell::Empty && would_win_board(cells, c, player)).count()
 }
 
-fn pick_move(state: &GameState, level: u32, moves: &[(usize, usize)]) -> (usize, usize) {
-    if level == 0 {
-        let non_winning: Vec<_> = moves.iter()
-            .filter(|&&(b, c)| !would_win_board(&state.cells[b], c, Cell::Red))
-            .copied().collect();
-        if !non_winning.is_empty() { return pick_random(&non_winning); }
-        return pick_random(moves);
+fn board_reason(state: &GameState, board_idx: usize, cell_idx: usize, player: Cell) -> Option<Explanation> {
+    let opp = opponent(player);
+    let board = board_idx;
+    let cells = &state.cells[board_idx];
+    let open = state.board_winners[board_idx] == Cell::Empty;
+    let wins_board = open && would_win_board(cells, cell_idx, player);
+
+    if wins_board && would_win_meta(&state.board_winners, board_idx, player) {
+        return Some(Explanation::WinsGame { board });
+    }
+    if wins_board && creates_meta_threat(&state.board_winners, board_idx, player) {
+        return Some(Explanation::WinsBoardThreatensGame { board });
+    }
+    if wins_board {
+        return Some(Explanation::WinsBoard { board });
     }
 
-    if level == 1 {
-        return pick_random(moves);
+    if open && would_win_board(cells, cell_idx, opp) {
+        return Some(Explanation::BlocksBoard { board, opponent: opp });
     }
 
-    if level >= 21 {
-        return best_move_two_ply(state, moves);
+    if creates_meta_threat(&state.board_winners, board_idx, player) {
+        return Some(Explanation::ThreatensGame { board });
     }
 
-    if level >= 20 {
-        return best_move_one_ply(state, moves);
+    if open && creates_fork(cells, cell_idx, player) {
+        return Some(Explanation::CreatesFork { board });
     }
 
-    let winning: Vec<_> = moves.iter()
-        .filter(|&&(b, c)| {
-            state.board_winners[b] == Cell::Empty
-                && would_win_board(&state.cells[b], c, Cell::Red)
-        })
-        .copied().collect();
-    if !winning.is_empty() {
-        if level >= 12 {
-            let meta_win: Vec<_> = winning.iter()
-                .filter(|&&(b, _)| would_win_meta(&state.board_winners, b, Cell::Red))
-                .copied().collect();
-            if !meta_win.is_empty() { return pick_random(&meta_win); }
-        }
-        if level >= 11 {
-            let defensive: Vec<_> = winning.iter()
-                .filter(|&&(b, _)| {
-                    state.cells[b].iter().enumerate().any(|(i, &cell)| {
-                        cell == Cell::Empty && would_win_board(&state.cells[b], i, Cell::Blue)
-                    })
-                })
-                .copied().collect();
-            if !defensive.is_empty() { return pick_random(&defensive); }
-        }
-        if level >= 16 {
-            let meta_threat: Vec<_> = winning.iter()
-                .filter(|&&(b, _)| creates_meta_threat(&state.board_winners, b, Cell::Red))
-                .copied().collect();
-            if !meta_threat.is_empty() { return pick_random(&meta_threat); }
-        }
-        return pick_random(&winning);
+    None
+}
+
+// Only the board the opponent is sent to matters here, so that board alone is checked.
+fn destination_reason(state: &GameState, board_idx: usize, cell_idx: usize, player: Cell) -> Option<Explanation> {
+    let opp = opponent(player);
+    let mut after = state.clone();
+    apply_move(&mut after, board_idx, cell_idx, player);
+    if side_to_move(&after) != Some(opp) {
+        return None;
     }
 
-    if level >= 3 {
-        let blocking: Vec<_> = moves.iter()
-            .filter(|&&(b, c)| {
-                state.board_winners[b] == Cell::Empty
-                    && would_win_board(&state.cells[b], c, Cell::Blue)
-            })
-            .copied().collect();
-        if !blocking.is_empty() {
-            if level >= 13 {
-                let meta_block: Vec<_> = blocking.iter()
-                    .filter(|&&(b, _)| would_win_meta(&state.board_winners, b, Cell::Blue))
-                    .copied().collect();
-                if !meta_block.is_empty() { return pick_random(&meta_block); }
-            }
-            return pick_random(&blocking);
+    let board = match after.required_board {
+        Some(d) => d,
+        None => return Some(Explanation::GivesFreeChoice { opponent: opp }),
+    };
+
+    if after.board_winners[board] != Cell::Empty {
+        return Some(Explanation::SendsToDecidedBoard { board, opponent: opp });
+    }
+    let cells = &after.cells[board];
+    if winning_cells(cells, opp) > 0 {
+        if would_win_meta(&after.board_winners, board, opp) {
+            return Some(Explanation::SendsToGameWin { board, opponent: opp });
         }
+        return Some(Explanation::SendsToBoardWin { board, opponent: opp });
     }
-
-    if level >= 4 {
-        let block_forks: Vec<_> = moves.iter()
-            .filter(|&&(b, c)| {
-                state.board_winners[b] == Cell::Empty
-                    && creates_fork(&state.cells[b], c, Cell::Blue)
-            })
-            .copied().collect();
-        if !block_forks.is_empty() { return pick_random(&block_forks); }
+    match winning_cells(cells, player) {
+        0 => None,
+        1 => Some(Explanation::ForcesDefense { board, opponent: opp }),
+        _ => Some(Explanation::ForcesIntoFork { board, opponent: opp }),
     }
+}
 
-    if level >= 19 {
-        let forks: Vec<_> = moves.iter()
-            .filter(|&&(b, c)| {
-                state.board_winners[b] == Cell::Empty
-                    && creates_fork(&state.cells[b], c, Cell::Red)
-            })
-            .copied().collect();
-        if !forks.is_empty() { return pick_random(&forks); }
-    }
-
-    let mut candidates = moves.to_vec();
-
-    if level >= 7 {
-        let safer: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| {
-                state.board_full[c]
-                    || state.board_winners[c] != Cell::Empty
-                    || {
-                        let blue = state.cells[c].iter().filter(|&&cell| cell == Cell::Blue).count();
-                        let red = state.cells[c].iter().filter(|&&cell| cell == Cell::Red).count();
-                        blue <= red
-                    }
-            })
-            .copied().collect();
-        if !safer.is_empty() { candidates = safer; }
-    }
-
-    if level >= 8 {
-        let safe: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| {
-                state.board_full[c]
-                    || state.board_winners[c] != Cell::Empty
-                    || !state.cells[c].iter().enumerate().any(|(i, &cell)| {
-                        cell == Cell::Empty && would_win_board(&state.cells[c], i, Cell::Blue)
-                    })
-            })
-            .copied().collect();
-        if !safe.is_empty() { candidates = safe; }
-    }
-
-    if level >= 9 {
-        let safe_fork: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| {
-                state.board_full[c]
-                    || state.board_winners[c] != Cell::Empty
-                    || !state.cells[c].iter().enumerate().any(|(i, &cell)| {
-                        cell == Cell::Empty && creates_fork(&state.cells[c], i, Cell::Blue)
-                    })
-            })
-            .copied().collect();
-        if !safe_fork.is_empty() { candidates = safe_fork; }
-    }
-
-    if level >= 17 {
-        let protect: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| {
-                state.board_full[c]
-                    || state.board_winners[c] != Cell::Empty
-                    || !WIN_LINES.iter().any(|line| {
-                        line.contains(&c)
-                            && state.board_winners[c] == Cell::Empty
-                            && line.iter().filter(|&&i| state.board_winners[i] == Cell::Red).count() == 2
-                    })
-            })
-            .copied().collect();
-        if !protect.is_empty() { candidates = protect; }
+pub fn generate_explanation(state: &GameState, board_idx: usize, cell_idx: usize, player: Cell) -> Vec<Explanation> {
+    let mut reasons: Vec<Explanation> = board_reason(state, board_idx, cell_idx, player).into_iter().collect();
+    reasons.extend(destination_reason(state, board_idx, cell_idx, player));
+    if reasons.is_empty() {
+        reasons.push(Explanation::Positional);
     }
+    reasons
+}
 
-    if level >= 18 {
-        let safe_meta: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| {
-                state.board_full[c]
-                    || state.board_winners[c] != Cell::Empty
-                    || (!would_win_meta(&state.board_winners, c, Cell::Blue)
-                        && !creates_meta_threat(&state.board_winners, c, Cell::Blue))
-            })
-            .copied().collect();
-        if !safe_meta.is_empty() { candidates = safe_meta; }
-    }
-
-    if level >= 10 {
-        let trap: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| {
-                if state.board_full[c] { return false; }
-                let empties: Vec<usize> = state.cells[c].iter().enumerate()
-                    .filter(|(_, &cell)| cell == Cell::Empty)
-                    .map(|(i, _)| i)
-                    .collect();
-                empties.len() == 1 && {
-                    let dest = empties[0];
-                    state.cells[dest].iter().enumerate().any(|(i, &cell)| {
-                        cell == Cell::Empty && would_win_board(&state.cells[dest], i, Cell::Red)
-                    })
-                }
-            })
-            .copied().collect();
-        if !trap.is_empty() { return pick_random(&trap); }
+// --- Coach ---
+
+// What the move hands the opponent straight away: the game, a board or a fork, worst first.
+// Moves are only flagged when a safer one exists.
+pub fn blunder_warning(state: &GameState, board_idx: usize, cell_idx: usize, player: Cell) -> Option<Explanation> {
+    let danger = |m: (usize, usize)| reply_danger(state, m, player);
+    let (rank, warning) = danger((board_idx, cell_idx))?;
+    let safer = legal_moves(state).into_iter().any(|m| danger(m).is_none_or(|(r, _)| r > rank));
+    safer.then_some(warning)
+}
+
+fn reply_danger(state: &GameState, (b, c): (usize, usize), player: Cell) -> Option<(u8, Explanation)> {
+    let opp = opponent(player);
+    let mut after = state.clone();
+    apply_move(&mut after, b, c, player);
+    if side_to_move(&after) != Some(opp) {
+        return None;
     }
+    let replies = legal_moves(&after);
+    let open = |rb: usize| after.board_winners[rb] == Cell::Empty;
+    let board_wins: Vec<_> = replies.iter()
+        .filter(|&&(rb, rc)| open(rb) && would_win_board(&after.cells[rb], rc, opp))
+        .copied().collect();
+    if let Some(&(board, _)) = board_wins.iter().find(|&&(rb, _)| would_win_meta(&after.board_winners, rb, opp)) {
+        return Some((0, Explanation::SendsToGameWin { board, opponent: opp }));
+    }
+    if let Some(&(board, _)) = board_wins.first() {
+        return Some((1, Explanation::SendsToBoardWin { board, opponent: opp }));
+    }
+    replies.iter()
+        .find(|&&(rb, rc)| open(rb) && creates_fork(&after.cells[rb], rc, opp))
+        .map(|&(board, _)| (2, Explanation::SendsToFork { board, opponent: opp }))
+}
 
-    if level >= 5 {
-        let to_empty: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| {
-                !state.board_full[c] && state.cells[c].iter().all(|&cell| cell == Cell::Empty)
-            })
-            .copied().collect();
-        if !to_empty.is_empty() {
-            if level >= 14 {
-                if let Some(m) = prefer_center(&to_empty) { return m; }
-            }
-            if level >= 15 {
-                if let Some(m) = prefer_corners(&to_empty) { return m; }
-            }
-            return pick_random(&to_empty);
+// --- Ranked hints ---
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HintStrength {
+    Beginner,
+    Standard,
+    Advanced,
+}
+
+impl HintStrength {
+    // Beginner hints don't search; they follow the level heuristics instead.
+    pub fn depth(self) -> Option<u32> {
+        match self {
+            HintStrength::Beginner => None,
+            HintStrength::Standard => Some(2),
+            HintStrength::Advanced => Some(4),
         }
     }
+}
 
-    if level >= 6 {
-        let empty_count = |c: usize| {
-            if state.board_full[c] { 0 }
-            else { state.cells[c].iter().filter(|&&cell| cell == Cell::Empty).count() }
-        };
-        let max_empty = candidates.iter().map(|&(_, c)| empty_count(c)).max().unwrap();
-        let most_empty: Vec<_> = candidates.iter()
-            .filter(|&&(_, c)| empty_count(c) == max_empty)
-            .copied().collect();
-        if level >= 14 {
-            if let Some(m) = prefer_center(&most_empty) { return m; }
+#[derive(Clone, Debug, Serialize)]
+pub struct RankedHint {
+    pub board_idx: usize,
+    pub cell_idx: usize,
+    pub score: i32,
+    pub explanation: Vec<Explanation>,
+}
+
+pub(crate) fn minimax(state: &GameState, depth: u32, mut alpha: i32, mut beta: i32, w: &EvalWeights) -> i32 {
+    let player = match side_to_move(state) {
+        Some(p) if depth > 0 => p,
+        _ => return evaluate_with(state, w),
+    };
+    let moves = legal_moves(state);
+    if moves.is_empty() { return evaluate_with(state, w); }
+    if player == Cell::Red {
+        let mut best = i32::MIN;
+        for (b, c) in moves {
+            let mut s = state.clone();
+            apply_move(&mut s, b, c, player);
+            best = best.max(minimax(&s, depth - 1, alpha, beta, w));
+            alpha = alpha.max(best);
+            if alpha >= beta { break; }
         }
-        if level >= 15 {
-            if let Some(m) = prefer_corners(&most_empty) { return m; }
+        best
+    } else {
+        let mut best = i32::MAX;
+        for (b, c) in moves {
+            let mut s = state.clone();
+            apply_move(&mut s, b, c, player);
+            best = best.min(minimax(&s, depth - 1, alpha, beta, w));
+            beta = beta.min(best);
+            if alpha >= beta { break; }
         }
-        return pick_random(&most_empty);
+        best
     }
+}
 
-    if level >= 14 {
-        if let Some(m) = prefer_center(&candidates) { return m; }
-    }
-    if level >= 15 {
-        if let Some(m) = prefer_corners(&candidates) { return m; }
-    }
+// Scores are from the mover's point of view: higher is better for the side to move.
+pub fn score_moves(state: &GameState, moves: &[(usize, usize)], depth: u32) -> Vec<((usize, usize), i32)> {
+    score_moves_with(state, moves, depth, &EvalWeights::default())
+}
+
+pub fn score_moves_with(state: &GameState, moves: &[(usize, usize)], depth: u32, w: &EvalWeights) -> Vec<((usize, usize), i32)> {
+    let player = match side_to_move(state) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    moves.iter().map(|&(b, c)| {
+        let mut s = state.clone();
+        apply_move(&mut s, b, c, player);
+        let score = minimax(&s, depth.saturating_sub(1), i32::MIN, i32::MAX, w);
+        ((b, c), if player == Cell::Red { score } else { -score })
+    }).collect()
+}
 
-    pick_random(&candidates)
+pub fn ranked_hints(state: &GameState, count: usize, strength: HintStrength) -> Vec<RankedHint> {
+    let player = match side_to_move(state) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    let moves = legal_moves(state);
+    let mut scored = match strength.depth() {
+        Some(depth) => score_moves(state, &moves, depth),
+        None => levels::heuristic_level().rank(state, player, &moves),
+    };
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored.into_iter().take(count).map(|((b, c), score)| RankedHint {
+        board_idx: b,
+        cell_idx: c,
+        score,
+        explanation: generate_explanation(state, b, c, player),
+    }).collect()
 }
 
-pub fn best_move_for_blue(state: &GameState, moves: &[(usize, usize)]) -> (usize, usize) {
-    let mut best_score = i32::MAX;
-    let mut best_moves = Vec::new();
-    for &(b, c) in moves {
-        let mut s1 = state.clone();
-        apply_move(&mut s1, b, c, Cell::Blue);
-        let score = if s1.status != GameStatus::RedToMove {
-            evaluate(&s1)
-        } else {
-            let red_moves = legal_moves(&s1);
-            red_moves.iter().map(|&(rb, rc)| {
-                let mut s2 = s1.clone();
-                apply_move(&mut s2, rb, rc, Cell::Red);
-                evaluate(&s2)
-            }).max().unwrap_or(evaluate(&s1))
-        };
-        if score < best_score {
-            best_score = score;
-            best_moves = vec![(b, c)];
-        } else if score == best_score {
-            best_moves.push((b, c));
-        }
+pub fn side_to_move(state: &GameState) -> Option<Cell> {
+    match state.status {
+        GameStatus::BlueToMove => Some(Cell::Blue),
+        GameStatus::RedToMove => Some(Cell::Red),
+        _ => None,
     }
-    best_moves[0]
 }
 
-fn generate_explanation(state: &GameState, board_idx: usize, cell_idx: usize) -> String {
-    let wins_board = state.board_winners[board_idx] == Cell::Empty
-        && would_win_board(&state.cells[board_idx], cell_idx, Cell::Blue);
-    let wins_meta = wins_board
-        && would_win_meta(&state.board_winners, board_idx, Cell::Blue);
+// --- Win probability ---
 
-    if wins_board && wins_meta {
-        return "Wins the game!".into();
-    }
-    if wins_board && creates_meta_threat(&state.board_winners, board_idx, Cell::Blue) {
-        return "Wins board and threatens the game".into();
-    }
-    if wins_board {
-        return "Wins a board".into();
-    }
+const PLAYOUTS: u32 = 300;
 
-    if state.board_winners[board_idx] == Cell::Empty
-        && would_win_board(&state.cells[board_idx], cell_idx, Cell::Red)
-    {
-        return "Blocks red from winning a board".into();
+fn playout_move(state: &GameState, player: Cell, moves: &[(usize, usize)]) -> (usize, usize) {
+    let board_wins: Vec<_> = moves.iter()
+        .filter(|&&(b, c)| {
+            state.board_winners[b] == Cell::Empty
+                && would_win_board(&state.cells[b], c, player)
+        })
+        .copied().collect();
+    if let Some(&m) = board_wins.iter().find(|&&(b, _)| would_win_meta(&state.board_winners, b, player)) {
+        return m;
     }
+    if !board_wins.is_empty() { return pick_random(&board_wins); }
+    pick_random(moves)
+}
 
-    if creates_meta_threat(&state.board_winners, board_idx, Cell::Blue) {
-        return "Threatens to win the game".into();
+fn playout(state: &GameState) -> GameStatus {
+    let mut s = state.clone();
+    while let Some(player) = side_to_move(&s) {
+        let moves = legal_moves(&s);
+        if moves.is_empty() { break; }
+        let (b, c) = playout_move(&s, player, &moves);
+        apply_move(&mut s, b, c, player);
     }
+    s.status
+}
 
-    if state.board_winners[board_idx] == Cell::Empty
-        && creates_fork(&state.cells[board_idx], cell_idx, Cell::Blue)
-    {
-        return "Creates two ways to win a board".into();
+pub fn win_probability(state: &GameState) -> WinProbability {
+    match state.status {
+        GameStatus::BlueWins => return WinProbability { blue: 1.0, draw: 0.0, red: 0.0 },
+        GameStatus::RedWins => return WinProbability { blue: 0.0, draw: 0.0, red: 1.0 },
+        GameStatus::Draw => return WinProbability { blue: 0.0, draw: 1.0, red: 0.0 },
+        _ => {}
     }
-
-    if state.board_full[cell_idx] {
-        return "Gives you a free choice next".into();
+    let (mut blue, mut red, mut draw) = (0u32, 0u32, 0u32);
+    for _ in 0..PLAYOUTS {
+        match playout(state) {
+            GameStatus::BlueWins => blue += 1,
+            GameStatus::RedWins => red += 1,
+            _ => draw += 1,
+        }
     }
+    let n = PLAYOUTS as f64;
+    WinProbability {
+        blue: blue as f64 / n,
+        draw: draw as f64 / n,
+        red: red as f64 / n,
+    }
+}
 
-    "Best positional move".into()
+pub fn evaluation_series(start: &GameState, moves: &[(usize, usize)]) -> Result<Vec<WinProbability>, String> {
+    let mut state = start.clone();
+    let mut series = vec![win_probability(&state)];
+    for &(b, c) in moves {
+        let player = side_to_move(&state).ok_or("Game is already over")?;
+        if !legal_moves(&state).contains(&(b, c)) {
+            return Err(format!("Illegal move ({}, {})", b, c));
+        }
+        apply_move(&mut state, b, c, player);
+        series.push(win_probability(&state));
+    }
+    Ok(series)
 }
 
 pub fn computer_move(state: &mut GameState, level: u32) {
+    computer_move_with(state, &levels::level_def(level));
+}
+
+pub fn computer_move_with(state: &mut GameState, def: &levels::LevelDef) {
+    computer_move_modeled(state, def, None);
+}
+
+pub fn computer_move_modeled(state: &mut GameState, def: &levels::LevelDef, model: Option<&model::OpponentModel>) {
+    let player = match side_to_move(state) {
+        Some(p) => p,
+        None => return,
+    };
     let moves = legal_moves(state);
     if moves.is_empty() { return; }
-    let chosen = pick_move(state, level, &moves);
-    apply_move(state, chosen.0, chosen.1, Cell::Red);
+    let chosen = def.choose_with_model(state, player, &moves, model);
+    apply_move(state, chosen.0, chosen.1, player);
 }
 
 // --- WASM exports ---
@@ -608,44 +881,119 @@ pub fn wasm_new_game() -> JsValue {
     serde_wasm_bindgen::to_value(&GameState::new()).unwrap()
 }
 
+// Throws on a handicap that doesn't parse as well as on one that does but can't be played.
 #[cfg(feature = "wasm")]
 #[wasm_bindgen]
-pub fn wasm_make_move(state_js: JsValue, board_idx: usize, cell_idx: usize, level: u32) -> JsValue {
-    let mut state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+pub fn wasm_new_game_with_handicap(handicap_js: JsValue) -> Result<JsValue, JsError> {
+    let handicap: Handicap = serde_wasm_bindgen::from_value(handicap_js).map_err(|e| JsError::new(&e.to_string()))?;
+    let state = GameState::with_handicap(handicap)?;
+    Ok(serde_wasm_bindgen::to_value(&state).unwrap())
+}
 
-    if board_idx >= 9 || cell_idx >= 9 {
-        return serde_wasm_bindgen::to_value(&MoveResponse {
-            ok: false,
-            state,
-            error: Some("Invalid indices".into()),
-        }).unwrap();
-    }
-    if state.status != GameStatus::BlueToMove {
-        return serde_wasm_bindgen::to_value(&MoveResponse {
+#[cfg(feature = "wasm")]
+fn blue_move_and_reply(mut state: GameState, board_idx: usize, cell_idx: usize, coach: bool, reply: impl Fn(&mut GameState)) -> MoveResponse {
+    if let Err(e) = check_move(&state, Cell::Blue, board_idx, cell_idx) {
+        return MoveResponse {
             ok: false,
             state,
-            error: Some("Not blue's turn".into()),
-        }).unwrap();
+            error: Some(e.to_string()),
+            model: None,
+            warning: None,
+        };
     }
-    if !legal_moves(&state).contains(&(board_idx, cell_idx)) {
-        return serde_wasm_bindgen::to_value(&MoveResponse {
-            ok: false,
-            state,
-            error: Some("Illegal move".into()),
-        }).unwrap();
+    // Coach mode hands the move back unplayed; the player confirms by sending it again without the check.
+    if coach {
+        if let Some(warning) = blunder_warning(&state, board_idx, cell_idx, Cell::Blue) {
+            return MoveResponse {
+                ok: false,
+                state,
+                error: None,
+                model: None,
+                warning: Some(warning),
+            };
+        }
     }
 
     apply_move(&mut state, board_idx, cell_idx, Cell::Blue);
 
-    if state.status == GameStatus::RedToMove {
-        computer_move(&mut state, level);
+    // An extra-move handicap can leave Red to move twice in a row. A malformed position sent by the client can
+    // leave Red with nothing to play, so a reply that doesn't move ends the loop.
+    while state.status == GameStatus::RedToMove {
+        let ply = state.ply;
+        reply(&mut state);
+        if state.ply == ply { break; }
     }
 
-    serde_wasm_bindgen::to_value(&MoveResponse {
+    MoveResponse {
         ok: true,
         state,
         error: None,
-    }).unwrap()
+        model: None,
+        warning: None,
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_make_move(state_js: JsValue, board_idx: usize, cell_idx: usize, level: u32, coach: bool) -> JsValue {
+    let state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    let response = blue_move_and_reply(state, board_idx, cell_idx, coach, |s| computer_move(s, level));
+    serde_wasm_bindgen::to_value(&response).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_make_move_modeled(state_js: JsValue, board_idx: usize, cell_idx: usize, level: u32, model_js: JsValue, coach: bool) -> JsValue {
+    let state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    let mut model: model::OpponentModel = serde_wasm_bindgen::from_value(model_js).unwrap_or_default();
+    let before = state.clone();
+    let def = levels::level_def(level);
+    let mut response = blue_move_and_reply(state, board_idx, cell_idx, coach, |s| computer_move_modeled(s, &def, Some(&model)));
+    if response.ok {
+        model.observe(&before, board_idx, cell_idx);
+    }
+    response.model = Some(model);
+    serde_wasm_bindgen::to_value(&response).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_make_move_personality(state_js: JsValue, board_idx: usize, cell_idx: usize, personality: &str, coach: bool) -> JsValue {
+    let state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    let response = match levels::personality(personality) {
+        Some(p) => blue_move_and_reply(state, board_idx, cell_idx, coach, |s| computer_move_with(s, &p.def)),
+        None => MoveResponse {
+            ok: false,
+            state,
+            error: Some("Unknown opponent".into()),
+            model: None,
+            warning: None,
+        },
+    };
+    serde_wasm_bindgen::to_value(&response).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_play_local(state_js: JsValue, board_idx: usize, cell_idx: usize, coach: bool) -> JsValue {
+    let mut state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    let warning = side_to_move(&state)
+        .filter(|&p| coach && check_move(&state, p, board_idx, cell_idx).is_ok())
+        .and_then(|p| blunder_warning(&state, board_idx, cell_idx, p));
+    let response = match warning {
+        Some(_) => MoveResponse { ok: false, state, error: None, model: None, warning },
+        None => match play_turn(&mut state, board_idx, cell_idx) {
+            Ok(_) => MoveResponse { ok: true, state, error: None, model: None, warning: None },
+            Err(e) => MoveResponse { ok: false, state, error: Some(e.to_string()), model: None, warning: None },
+        },
+    };
+    serde_wasm_bindgen::to_value(&response).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_personalities() -> JsValue {
+    serde_wasm_bindgen::to_value(levels::personalities()).unwrap()
 }
 
 #[cfg(feature = "wasm")]
@@ -657,7 +1005,8 @@ pub fn wasm_get_hint(state_js: JsValue) -> JsValue {
         return serde_wasm_bindgen::to_value(&HintResponse {
             board_idx: 0,
             cell_idx: 0,
-            explanation: "Not blue's turn".into(),
+            explanation: Vec::new(),
+            error: Some("Not blue's turn".into()),
         }).unwrap();
     }
 
@@ -666,16 +1015,155 @@ pub fn wasm_get_hint(state_js: JsValue) -> JsValue {
         return serde_wasm_bindgen::to_value(&HintResponse {
             board_idx: 0,
             cell_idx: 0,
-            explanation: "No legal moves".into(),
+            explanation: Vec::new(),
+            error: Some("No legal moves".into()),
         }).unwrap();
     }
 
     let (b, c) = best_move_for_blue(&state, &moves);
-    let explanation = generate_explanation(&state, b, c);
+    let explanation = generate_explanation(&state, b, c, Cell::Blue);
 
     serde_wasm_bindgen::to_value(&HintResponse {
         board_idx: b,
         cell_idx: c,
         explanation,
+        error: None,
     }).unwrap()
 }
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_win_probability(state_js: JsValue) -> JsValue {
+    let state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    serde_wasm_bindgen::to_value(&win_probability(&state)).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_evaluation_series(start_js: JsValue, moves_js: JsValue) -> JsValue {
+    let start: GameState = serde_wasm_bindgen::from_value(start_js).unwrap();
+    let moves: Vec<(usize, usize)> = serde_wasm_bindgen::from_value(moves_js).unwrap();
+    match evaluation_series(&start, &moves) {
+        Ok(series) => serde_wasm_bindgen::to_value(&series).unwrap(),
+        Err(_) => JsValue::NULL,
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_get_hints(state_js: JsValue, count: usize, strength_js: JsValue) -> JsValue {
+    let state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    let strength: HintStrength = serde_wasm_bindgen::from_value(strength_js).unwrap_or(HintStrength::Standard);
+    serde_wasm_bindgen::to_value(&ranked_hints(&state, count, strength)).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_render_explanation(explanation_js: JsValue, locale: &str) -> String {
+    let explanation: Vec<Explanation> = serde_wasm_bindgen::from_value(explanation_js).unwrap_or_default();
+    i18n::render(&explanation, i18n::Locale::from_tag(locale))
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_render_lesson_text(code: &str, locale: &str) -> String {
+    i18n::lesson_text(code, i18n::Locale::from_tag(locale))
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_threats(state_js: JsValue) -> JsValue {
+    let state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    serde_wasm_bindgen::to_value(&threats(&state)).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_levels() -> JsValue {
+    serde_wasm_bindgen::to_value(&*levels::levels()).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_set_levels(json: &str) -> bool {
+    match levels::parse_levels(json) {
+        Ok(defs) => {
+            levels::set_levels(defs);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_record_result(progress_js: JsValue, outcome_js: JsValue, assisted: bool, handicap_js: JsValue, personality: Option<String>) -> JsValue {
+    let mut progress: progress::PlayerProgress = serde_wasm_bindgen::from_value(progress_js).unwrap_or_default();
+    let outcome: progress::Outcome = serde_wasm_bindgen::from_value(outcome_js).unwrap();
+    let handicap: Option<Handicap> = serde_wasm_bindgen::from_value(handicap_js).unwrap_or(None);
+    match personality.as_deref().and_then(levels::personality) {
+        Some(p) => progress.record_personality_game(outcome, assisted, p, handicap.as_ref(), Cell::Blue),
+        None => { progress.record_game(outcome, assisted, handicap.as_ref(), Cell::Blue); }
+    }
+    serde_wasm_bindgen::to_value(&progress).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_decode_position(position: &str) -> JsValue {
+    match notation::decode(position) {
+        Ok(state) => serde_wasm_bindgen::to_value(&state).unwrap(),
+        Err(_) => JsValue::NULL,
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_encode_position(state_js: JsValue) -> String {
+    let state: GameState = serde_wasm_bindgen::from_value(state_js).unwrap();
+    notation::encode(&state)
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_puzzles() -> JsValue {
+    serde_wasm_bindgen::to_value(puzzle::puzzles()).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_puzzle_start(id: &str) -> JsValue {
+    match puzzle::puzzle(id).map(puzzle::PuzzleSession::new) {
+        Some(Ok(session)) => serde_wasm_bindgen::to_value(&session).unwrap(),
+        _ => JsValue::NULL,
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_puzzle_move(session_js: JsValue, board_idx: usize, cell_idx: usize) -> JsValue {
+    let session: puzzle::PuzzleSession = serde_wasm_bindgen::from_value(session_js).unwrap();
+    serde_wasm_bindgen::to_value(&session.play(board_idx, cell_idx)).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_lessons() -> JsValue {
+    serde_wasm_bindgen::to_value(lessons::lessons()).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_lesson_start(id: &str) -> JsValue {
+    match lessons::lesson(id).map(lessons::LessonSession::new) {
+        Some(Ok(session)) => serde_wasm_bindgen::to_value(&session).unwrap(),
+        _ => JsValue::NULL,
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn wasm_lesson_move(session_js: JsValue, board_idx: usize, cell_idx: usize) -> JsValue {
+    let session: lessons::LessonSession = serde_wasm_bindgen::from_value(session_js).unwrap();
+    serde_wasm_bindgen::to_value(&session.attempt(board_idx, cell_idx)).unwrap()
+}
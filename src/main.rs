@@ -1,15 +1,54 @@
-use axum::{routing::get, Router};
-use tower_http::services::ServeDir;
+use std::sync::Arc;
 
-async fn handle_index() -> axum::response::Html<&'static str> {
-    axum::response::Html(include_str!("../static/index.html"))
+use ttt::levels;
+use ttt::server::notify::{LogNotifier, Notifier, WebhookNotifier};
+use ttt::server::store::{MemoryStore, Repository, SqliteStore};
+use ttt::server::{self, correspondence, rooms, AppState};
+
+fn load_levels() {
+    let path = match std::env::var("TTT_LEVELS") {
+        Ok(p) => p,
+        Err(_) => return,
+    };
+    let json = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+    let defs = levels::parse_levels(&json)
+        .unwrap_or_else(|e| panic!("Invalid levels in {}: {}", path, e));
+    println!("Loaded {} levels from {}", defs.len(), path);
+    levels::set_levels(defs);
+}
+
+// TTT_DB names the SQLite file; ":memory:" keeps everything in memory for the life of the process.
+fn open_store() -> Arc<dyn Repository> {
+    let path = std::env::var("TTT_DB").unwrap_or_else(|_| "ttt.db".to_string());
+    if path == ":memory:" {
+        return Arc::new(MemoryStore::new());
+    }
+    let store = SqliteStore::open(&path).unwrap_or_else(|e| panic!("Failed to open {}: {}", path, e));
+    println!("Using database {}", path);
+    Arc::new(store)
+}
+
+// TTT_WEBHOOK sends correspondence notices to a URL; otherwise they're logged, to TTT_NOTIFY_LOG if set.
+fn open_notifier() -> Arc<dyn Notifier> {
+    if let Ok(url) = std::env::var("TTT_WEBHOOK") {
+        println!("Sending notices to {}", url);
+        return Arc::new(WebhookNotifier::new(url));
+    }
+    match std::env::var("TTT_NOTIFY_LOG") {
+        Ok(path) => Arc::new(LogNotifier::file(&path).unwrap_or_else(|e| panic!("Failed to open {}: {}", path, e))),
+        Err(_) => Arc::new(LogNotifier::stdout()),
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let app = Router::new()
-        .route("/", get(handle_index))
-        .fallback_service(ServeDir::new("static"));
+    load_levels();
+
+    let state = AppState::new(open_store()).with_notifier(open_notifier());
+    tokio::spawn(correspondence::sweep_deadlines(state.clone()));
+    tokio::spawn(rooms::sweep_rooms(state.clone()));
+    let app = server::router(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     println!("Listening on http://0.0.0.0:3000");
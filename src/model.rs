@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    apply_move, legal_moves, minimax, opponent, side_to_move, would_win_board, Cell, EvalWeights,
+    GameState,
+};
+
+// Observations needed before the model is trusted as much as the worst case.
+const CONFIDENCE_OBSERVATIONS: f64 = 30.0;
+const TACTIC_BOOST: f64 = 4.0;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpponentModel {
+    pub observations: u32,
+    pub forced_cells: [u32; 9],
+    pub free_boards: [u32; 9],
+    pub free_cells: [u32; 9],
+    pub win_chances: u32,
+    pub wins_taken: u32,
+    pub block_chances: u32,
+    pub blocks_made: u32,
+}
+
+fn ratio(hits: u32, chances: u32) -> f64 {
+    (hits as f64 + 1.0) / (chances as f64 + 2.0)
+}
+
+fn smoothed(counts: &[u32; 9], idx: usize) -> f64 {
+    let total: u32 = counts.iter().sum();
+    (counts[idx] as f64 + 1.0) / (total as f64 + 9.0)
+}
+
+fn wins_board(state: &GameState, (b, c): (usize, usize), player: Cell) -> bool {
+    state.board_winners[b] == Cell::Empty && would_win_board(&state.cells[b], c, player)
+}
+
+impl OpponentModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Call with the position before the modeled player's move is applied.
+    pub fn observe(&mut self, state: &GameState, board_idx: usize, cell_idx: usize) {
+        let player = match side_to_move(state) {
+            Some(p) => p,
+            None => return,
+        };
+        let opp = opponent(player);
+        let moves = legal_moves(state);
+        let chosen = (board_idx, cell_idx);
+
+        self.observations += 1;
+        if state.required_board.is_some() {
+            self.forced_cells[cell_idx] += 1;
+        } else {
+            self.free_boards[board_idx] += 1;
+            self.free_cells[cell_idx] += 1;
+        }
+        if moves.iter().any(|&m| wins_board(state, m, player)) {
+            self.win_chances += 1;
+            if wins_board(state, chosen, player) { self.wins_taken += 1; }
+        }
+        if moves.iter().any(|&m| wins_board(state, m, opp)) {
+            self.block_chances += 1;
+            if wins_board(state, chosen, opp) { self.blocks_made += 1; }
+        }
+    }
+
+    pub fn confidence(&self) -> f64 {
+        let n = self.observations as f64;
+        n / (n + CONFIDENCE_OBSERVATIONS)
+    }
+
+    // Probability of each move in `moves` being the modeled player's choice.
+    pub fn predict(&self, state: &GameState, moves: &[(usize, usize)]) -> Vec<f64> {
+        let player = side_to_move(state).unwrap_or(Cell::Blue);
+        let opp = opponent(player);
+        let p_win = ratio(self.wins_taken, self.win_chances);
+        let p_block = ratio(self.blocks_made, self.block_chances);
+        let weights: Vec<f64> = moves.iter().map(|&(b, c)| {
+            let mut w = if state.required_board.is_some() {
+                smoothed(&self.forced_cells, c)
+            } else {
+                smoothed(&self.free_boards, b) * smoothed(&self.free_cells, c)
+            };
+            if wins_board(state, (b, c), player) { w *= 1.0 + TACTIC_BOOST * p_win; }
+            if wins_board(state, (b, c), opp) { w *= 1.0 + TACTIC_BOOST * p_block; }
+            w
+        }).collect();
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return vec![1.0 / moves.len() as f64; moves.len()];
+        }
+        weights.iter().map(|w| w / total).collect()
+    }
+}
+
+// Like `score_moves_with`, but the modeled player's replies are weighted by how likely they are,
+// blended with the plain search score while the model is still thin. A depth 1 search looks one
+// predicted reply further than it otherwise would, so levels searching a single ply adapt too.
+// Levels without a search pick by heuristics alone and never consult the model.
+pub fn score_moves_modeled(
+    state: &GameState,
+    moves: &[(usize, usize)],
+    depth: u32,
+    w: &EvalWeights,
+    model: &OpponentModel,
+) -> Vec<((usize, usize), i32)> {
+    let player = match side_to_move(state) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    let sign = if player == Cell::Red { 1.0 } else { -1.0 };
+    let confidence = model.confidence();
+    moves.iter().map(|&(b, c)| {
+        let mut s1 = state.clone();
+        apply_move(&mut s1, b, c, player);
+        let replies = legal_moves(&s1);
+        let plain = || sign * minimax(&s1, depth.saturating_sub(1), i32::MIN, i32::MAX, w) as f64;
+        let score = if side_to_move(&s1) != Some(opponent(player)) || replies.is_empty() || depth == 0 {
+            plain()
+        } else {
+            let probs = model.predict(&s1, &replies);
+            let mut expected = 0.0;
+            let mut worst = f64::MAX;
+            for (&(rb, rc), p) in replies.iter().zip(probs) {
+                let mut s2 = s1.clone();
+                apply_move(&mut s2, rb, rc, opponent(player));
+                let v = sign * minimax(&s2, depth.saturating_sub(2), i32::MIN, i32::MAX, w) as f64;
+                expected += p * v;
+                worst = worst.min(v);
+            }
+            // At depth 2 and up the worst reply is the plain score; at depth 1 it would see further than the level does.
+            let fallback = if depth >= 2 { worst } else { plain() };
+            confidence * expected + (1.0 - confidence) * fallback
+        };
+        ((b, c), score.round() as i32)
+    }).collect()
+}
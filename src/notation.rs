@@ -0,0 +1,98 @@
+use crate::{check_winner, is_board_full, is_dead, Cell, GameState, GameStatus};
+
+// A position as one line: the nine boards' cells joined by '/', the board winners, the side to move
+// and the required board ('-' for a free choice). Cells and winners use '.', 'b' and 'r'.
+
+fn cell_char(cell: Cell) -> char {
+    match cell {
+        Cell::Empty => '.',
+        Cell::Blue => 'b',
+        Cell::Red => 'r',
+    }
+}
+
+fn parse_cell(ch: char) -> Result<Cell, String> {
+    match ch {
+        '.' => Ok(Cell::Empty),
+        'b' => Ok(Cell::Blue),
+        'r' => Ok(Cell::Red),
+        _ => Err(format!("Unexpected '{}'", ch)),
+    }
+}
+
+fn parse_row(text: &str) -> Result<[Cell; 9], String> {
+    let cells: Vec<Cell> = text.chars().map(parse_cell).collect::<Result<_, _>>()?;
+    cells.try_into().map_err(|_| format!("Expected 9 squares in '{}'", text))
+}
+
+pub fn encode(state: &GameState) -> String {
+    let boards: Vec<String> = state.cells.iter()
+        .map(|board| board.iter().map(|&c| cell_char(c)).collect())
+        .collect();
+    let winners: String = state.board_winners.iter().map(|&c| cell_char(c)).collect();
+    let side = match state.status {
+        GameStatus::RedToMove | GameStatus::BlueWins => 'r',
+        _ => 'b',
+    };
+    let required = state.required_board.map_or('-', |b| (b'0' + b as u8) as char);
+    format!("{} {} {} {}", boards.join("/"), winners, side, required)
+}
+
+pub fn decode(text: &str) -> Result<GameState, String> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    if parts.len() != 4 {
+        return Err("Expected boards, winners, side and required board".into());
+    }
+    let boards: Vec<&str> = parts[0].split('/').collect();
+    if boards.len() != 9 {
+        return Err("Expected 9 boards".into());
+    }
+
+    let mut state = GameState::new();
+    for (b, text) in boards.iter().enumerate() {
+        state.cells[b] = parse_row(text)?;
+        state.board_full[b] = is_board_full(&state.cells[b]);
+    }
+    state.board_winners = parse_row(parts[1])?;
+    for b in 0..9 {
+        let w = state.board_winners[b];
+        if w == Cell::Empty && check_winner(&state.cells[b]) != Cell::Empty {
+            return Err(format!("Board {} has a line but no winner", b));
+        }
+    }
+    state.ply = state.cells.iter().flatten().filter(|&&c| c != Cell::Empty).count() as u32;
+
+    let side = match parts[2] {
+        "b" => Cell::Blue,
+        "r" => Cell::Red,
+        s => return Err(format!("Unknown side '{}'", s)),
+    };
+    state.required_board = match parts[3] {
+        "-" => None,
+        s => match s.parse::<usize>() {
+            Ok(b) if b < 9 && !state.board_full[b] => Some(b),
+            _ => return Err(format!("Invalid required board '{}'", s)),
+        },
+    };
+
+    state.status = match check_winner(&state.board_winners) {
+        Cell::Blue => GameStatus::BlueWins,
+        Cell::Red => GameStatus::RedWins,
+        _ if is_dead(&state.board_winners)
+            || (0..9).all(|b| state.board_winners[b] != Cell::Empty || state.board_full[b]) => GameStatus::Draw,
+        Cell::Empty if side == Cell::Blue => GameStatus::BlueToMove,
+        Cell::Empty => GameStatus::RedToMove,
+    };
+    // Blue moves first, so Blue has as many marks as Red on its own turn and one more on Red's.
+    let count = |p| state.cells.iter().flatten().filter(|&&c| c == p).count();
+    let (blue, red) = (count(Cell::Blue), count(Cell::Red));
+    let possible = match state.status {
+        GameStatus::BlueToMove => blue == red,
+        GameStatus::RedToMove => blue == red + 1,
+        _ => blue == red || blue == red + 1,
+    };
+    if !possible {
+        return Err(format!("{} blue and {} red marks can't come about with {} to move", blue, red, parts[2]));
+    }
+    Ok(state)
+}
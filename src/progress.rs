@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+
+use crate::levels::{self, Personality};
+use crate::rating::{self, Rating};
+use crate::{Cell, GameStatus, Handicap};
+
+const STREAK_TO_CHANGE: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    #[serde(alias = "blue")]
+    Win,
+    #[serde(alias = "red")]
+    Loss,
+    Draw,
+}
+
+impl Outcome {
+    // The result for `color`, once the game is over.
+    pub fn from_status(status: GameStatus, color: Cell) -> Option<Self> {
+        match status {
+            GameStatus::Draw => Some(Outcome::Draw),
+            GameStatus::BlueWins => Some(if color == Cell::Blue { Outcome::Win } else { Outcome::Loss }),
+            GameStatus::RedWins => Some(if color == Cell::Red { Outcome::Win } else { Outcome::Loss }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelChange {
+    Up,
+    Down,
+    Same,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerProgress {
+    pub level: u32,
+    pub history: Vec<Outcome>,
+    pub streak: i32,
+    pub best_streak: u32,
+    pub highest_level: u32,
+    pub games: u32,
+    pub rating: Rating,
+}
+
+impl PlayerProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Assisted games (hint or undo used) don't count towards promotion or demotion.
+    pub fn record(&mut self, outcome: Outcome, assisted: bool) -> LevelChange {
+        self.record_game(outcome, assisted, None, Cell::Blue)
+    }
+
+    // Takes the ladder position reached while playing offline. Rating, games and streaks only change
+    // through games the server saw.
+    pub fn sync_ladder(&mut self, level: u32, mut history: Vec<Outcome>) {
+        history.drain(..history.len().saturating_sub(STREAK_TO_CHANGE));
+        self.level = level.min(levels::max_level());
+        self.history = history;
+    }
+
+    // Personality games are rated against the personality but leave the ladder and streaks alone.
+    pub fn record_personality_game(&mut self, outcome: Outcome, assisted: bool, personality: &Personality, handicap: Option<&Handicap>, color: Cell) {
+        if assisted { return; }
+        self.record_rated_game(outcome, &rating::personality_rating(personality), handicap, color);
+    }
+
+    // A game off the ladder, against another person or a bot away from the player's level, only moves the rating.
+    pub fn record_rated_game(&mut self, outcome: Outcome, opponent: &Rating, handicap: Option<&Handicap>, color: Cell) {
+        self.games += 1;
+        let opponent = rating::handicap_adjusted(opponent, handicap, color);
+        self.rating = rating::update_one(&self.rating, &opponent, outcome);
+    }
+
+    // Handicap games are rated with the handicap priced in, but never move the player along the ladder.
+    pub fn record_game(&mut self, outcome: Outcome, assisted: bool, handicap: Option<&Handicap>, color: Cell) -> LevelChange {
+        if assisted {
+            return LevelChange::Same;
+        }
+        self.games += 1;
+        self.highest_level = self.highest_level.max(self.level);
+        let bot = rating::handicap_adjusted(&rating::bot_rating(self.level), handicap, color);
+        self.rating = rating::update_one(&self.rating, &bot, outcome);
+        if handicap.is_some() {
+            return LevelChange::Same;
+        }
+        self.streak = match outcome {
+            Outcome::Win => self.streak.max(0) + 1,
+            Outcome::Loss => self.streak.min(0) - 1,
+            Outcome::Draw => 0,
+        };
+        self.best_streak = self.best_streak.max(self.streak.max(0) as u32);
+
+        self.history.push(outcome);
+        if self.history.len() > STREAK_TO_CHANGE {
+            self.history.drain(..self.history.len() - STREAK_TO_CHANGE);
+        }
+        if self.history.len() < STREAK_TO_CHANGE {
+            return LevelChange::Same;
+        }
+
+        let mut change = LevelChange::Same;
+        if self.history.iter().all(|&o| o == Outcome::Win) {
+            self.history.clear();
+            if self.level < levels::max_level() {
+                self.level += 1;
+                change = LevelChange::Up;
+            }
+        } else if self.history.iter().all(|&o| o == Outcome::Loss) {
+            self.history.clear();
+            if self.level > 0 {
+                self.level -= 1;
+                change = LevelChange::Down;
+            }
+        }
+        self.highest_level = self.highest_level.max(self.level);
+        change
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_level(level: u32) -> PlayerProgress {
+        PlayerProgress { level, ..PlayerProgress::new() }
+    }
+
+    #[test]
+    fn two_wins_promote_and_two_losses_demote() {
+        let mut p = at_level(3);
+        assert_eq!(p.record(Outcome::Win, false), LevelChange::Same);
+        assert_eq!(p.record(Outcome::Win, false), LevelChange::Up);
+        assert_eq!((p.level, p.highest_level, p.streak), (4, 4, 2));
+        assert!(p.history.is_empty());
+
+        assert_eq!(p.record(Outcome::Loss, false), LevelChange::Same);
+        assert_eq!(p.record(Outcome::Loss, false), LevelChange::Down);
+        assert_eq!((p.level, p.highest_level, p.streak, p.best_streak), (3, 4, -2, 2));
+
+        // A mixed pair moves nothing.
+        p.record(Outcome::Win, false);
+        assert_eq!(p.record(Outcome::Loss, false), LevelChange::Same);
+        assert_eq!(p.level, 3);
+    }
+
+    #[test]
+    fn assisted_games_are_ignored() {
+        let mut p = at_level(3);
+        p.record(Outcome::Win, false);
+        assert_eq!(p.record(Outcome::Win, true), LevelChange::Same);
+        assert_eq!((p.level, p.games, p.streak), (3, 1, 1));
+        assert_eq!(p.history, [Outcome::Win]);
+        assert_eq!(p.record(Outcome::Win, false), LevelChange::Up);
+    }
+
+    #[test]
+    fn draws_reset_the_streak() {
+        let mut p = at_level(3);
+        p.record(Outcome::Win, false);
+        assert_eq!(p.record(Outcome::Draw, false), LevelChange::Same);
+        assert_eq!(p.streak, 0);
+        // The draw also breaks up the pair of wins.
+        assert_eq!(p.record(Outcome::Win, false), LevelChange::Same);
+        assert_eq!((p.level, p.streak, p.best_streak), (3, 1, 1));
+    }
+
+    #[test]
+    fn level_stays_within_the_ladder() {
+        let mut p = at_level(0);
+        p.record(Outcome::Loss, false);
+        assert_eq!(p.record(Outcome::Loss, false), LevelChange::Same);
+        assert_eq!(p.level, 0);
+
+        let top = levels::max_level();
+        let mut p = at_level(top);
+        p.record(Outcome::Win, false);
+        assert_eq!(p.record(Outcome::Win, false), LevelChange::Same);
+        assert_eq!((p.level, p.highest_level), (top, top));
+    }
+
+    #[test]
+    fn handicap_games_leave_the_ladder_alone() {
+        let handicap = Handicap::ExtraMove { player: Cell::Blue };
+        let mut p = at_level(3);
+        let before = p.rating;
+        for _ in 0..3 {
+            assert_eq!(p.record_game(Outcome::Win, false, Some(&handicap), Cell::Blue), LevelChange::Same);
+        }
+        assert_eq!((p.level, p.streak, p.games), (3, 0, 3));
+        assert!(p.history.is_empty());
+        assert!(p.rating.rating > before.rating);
+    }
+}
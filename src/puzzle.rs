@@ -0,0 +1,424 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::levels::{self, Heuristic};
+use crate::{
+    apply_move, computer_move, creates_fork, legal_moves, notation, opponent, side_to_move,
+    would_win_board, Cell, GameState, GameStatus, WIN_LINES,
+};
+
+const PUZZLES: &str = include_str!("puzzles.json");
+
+static PUZZLE_SET: OnceLock<Vec<Puzzle>> = OnceLock::new();
+
+const BASE_RATING: u32 = 1000;
+const RATING_PER_MOVE: u32 = 250;
+const RATING_PER_ALTERNATIVE: u32 = 5;
+const QUIET_KEY_BONUS: u32 = 150;
+const TRAP_SQUARES: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    Fork,
+    MetaThreat,
+    SendTrap,
+    // The key wins no board, so the win comes from where it sends the defender.
+    QuietMove,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub id: String,
+    pub position: String,
+    pub player: Cell,
+    pub moves: u32,
+    pub solution: Vec<(usize, usize)>,
+    pub rating: u32,
+    pub themes: Vec<Theme>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PuzzleStatus {
+    Solving,
+    Solved,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PuzzleSession {
+    pub puzzle_id: String,
+    pub state: GameState,
+    pub player: Cell,
+    pub moves_left: u32,
+    pub status: PuzzleStatus,
+    // The solver's moves with the defence's replies in between. A move that lost the win ends it unplayed,
+    // so replaying the history fails the puzzle again.
+    pub history: Vec<(usize, usize)>,
+}
+
+#[derive(Serialize)]
+pub struct PuzzleStep {
+    pub ok: bool,
+    pub session: PuzzleSession,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<(usize, usize)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub struct GeneratorSettings {
+    pub games: u32,
+    pub min_moves: u32,
+    pub max_moves: u32,
+    pub max_branching: usize,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        Self {
+            games: 200,
+            min_moves: 2,
+            max_moves: 3,
+            max_branching: 30,
+        }
+    }
+}
+
+fn win_status(player: Cell) -> GameStatus {
+    if player == Cell::Red { GameStatus::RedWins } else { GameStatus::BlueWins }
+}
+
+// Fewest board wins `player` still needs to complete a meta line, since each move wins at most one board.
+fn boards_needed(state: &GameState, player: Cell) -> u32 {
+    let opp = opponent(player);
+    WIN_LINES.iter()
+        .filter(|line| line.iter().all(|&b| {
+            state.board_winners[b] != opp && (state.board_winners[b] == player || !state.board_full[b])
+        }))
+        .map(|line| line.iter().filter(|&&b| state.board_winners[b] != player).count() as u32)
+        .min()
+        .unwrap_or(u32::MAX)
+}
+
+fn after(state: &GameState, (b, c): (usize, usize), player: Cell) -> GameState {
+    let mut s = state.clone();
+    apply_move(&mut s, b, c, player);
+    s
+}
+
+// True if `attacker` wins the game within `moves` of their own moves, whatever the defence.
+pub fn wins_within(state: &GameState, attacker: Cell, moves: u32) -> bool {
+    let player = match side_to_move(state) {
+        Some(p) => p,
+        None => return state.status == win_status(attacker),
+    };
+    if boards_needed(state, attacker) > moves {
+        return false;
+    }
+    let options = legal_moves(state);
+    if player == attacker {
+        options.iter().any(|&m| wins_within(&after(state, m, player), attacker, moves - 1))
+    } else {
+        !options.is_empty() && options.iter().all(|&m| wins_within(&after(state, m, player), attacker, moves))
+    }
+}
+
+pub fn fastest_win(state: &GameState, attacker: Cell, max_moves: u32) -> Option<u32> {
+    (0..=max_moves).find(|&n| wins_within(state, attacker, n))
+}
+
+// Moves for the side to move that keep a win within `moves` (counting this one).
+pub fn winning_moves(state: &GameState, moves: u32) -> Vec<(usize, usize)> {
+    let player = match side_to_move(state) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    if moves == 0 { return Vec::new(); }
+    legal_moves(state).into_iter()
+        .filter(|&m| wins_within(&after(state, m, player), player, moves - 1))
+        .collect()
+}
+
+// The defence that holds out longest, so the line shows the whole idea.
+pub fn best_defence(state: &GameState, attacker: Cell, max_moves: u32) -> Option<(usize, usize)> {
+    let player = side_to_move(state)?;
+    legal_moves(state).into_iter()
+        .max_by_key(|&m| fastest_win(&after(state, m, player), attacker, max_moves).unwrap_or(u32::MAX))
+}
+
+fn solution_line(state: &GameState, attacker: Cell, moves: u32) -> Vec<(usize, usize)> {
+    let mut s = state.clone();
+    let mut line = Vec::new();
+    let mut left = moves;
+    while let Some(player) = side_to_move(&s) {
+        let m = if player == attacker {
+            let m = match winning_moves(&s, left).first() {
+                Some(&m) => m,
+                None => break,
+            };
+            left -= 1;
+            m
+        } else {
+            match best_defence(&s, attacker, left) {
+                Some(m) => m,
+                None => break,
+            }
+        };
+        line.push(m);
+        apply_move(&mut s, m.0, m.1, player);
+    }
+    line
+}
+
+// Sends the defender to an almost-full board where every square left sends them to their loss.
+fn sets_send_trap(state: &GameState, attacker: Cell, m: (usize, usize)) -> bool {
+    if !Heuristic::SendTrap.apply(state, attacker, &[m]).is_empty() {
+        return true;
+    }
+    let s = after(state, m, attacker);
+    let defender = opponent(attacker);
+    let replies = legal_moves(&s);
+    s.required_board.is_some()
+        && side_to_move(&s) == Some(defender)
+        && replies.len() <= TRAP_SQUARES
+        && replies.iter().all(|&r| wins_within(&after(&s, r, defender), attacker, 1))
+}
+
+// Themes of the key move, which is where the idea of the puzzle lies.
+fn themes(state: &GameState, attacker: Cell, (b, c): (usize, usize)) -> Vec<Theme> {
+    let mut found = Vec::new();
+    if state.board_winners[b] == Cell::Empty && creates_fork(&state.cells[b], c, attacker) {
+        found.push(Theme::Fork);
+    }
+    if !Heuristic::WinMetaThreat.apply(state, attacker, &[(b, c)]).is_empty() {
+        found.push(Theme::MetaThreat);
+    }
+    if sets_send_trap(state, attacker, (b, c)) {
+        found.push(Theme::SendTrap);
+    }
+    if is_quiet(state, attacker, (b, c)) {
+        found.push(Theme::QuietMove);
+    }
+    found
+}
+
+fn is_quiet(state: &GameState, attacker: Cell, (b, c): (usize, usize)) -> bool {
+    state.board_winners[b] != Cell::Empty || !would_win_board(&state.cells[b], c, attacker)
+}
+
+fn rating(state: &GameState, moves: u32, key: (usize, usize), attacker: Cell) -> u32 {
+    let alternatives = legal_moves(state).len() as u32 - 1;
+    BASE_RATING
+        + RATING_PER_MOVE * (moves - 1)
+        + RATING_PER_ALTERNATIVE * alternatives
+        + if is_quiet(state, attacker, key) { QUIET_KEY_BONUS } else { 0 }
+}
+
+fn puzzle_id(position: &str) -> String {
+    // FNV-1a, so the same position always gets the same id.
+    let hash = position.bytes().fold(0xcbf29ce484222325u64, |h, byte| {
+        (h ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+// A puzzle if the side to move has exactly one move that wins fastest, in `min_moves..=max_moves` moves.
+pub fn find_puzzle(state: &GameState, min_moves: u32, max_moves: u32) -> Option<Puzzle> {
+    let attacker = side_to_move(state)?;
+    let moves = fastest_win(state, attacker, max_moves)?;
+    if moves < min_moves.max(1) {
+        return None;
+    }
+    let keys = winning_moves(state, moves);
+    if keys.len() != 1 {
+        return None;
+    }
+    // Every puzzle names its idea; a position whose key fits no theme is left out.
+    let themes = themes(state, attacker, keys[0]);
+    if themes.is_empty() {
+        return None;
+    }
+    let solution = solution_line(state, attacker, moves);
+    let position = notation::encode(state);
+    Some(Puzzle {
+        id: puzzle_id(&position),
+        position,
+        player: attacker,
+        moves,
+        rating: rating(state, moves, keys[0], attacker),
+        themes,
+        solution,
+    })
+}
+
+// Plays games between randomly chosen levels and collects every puzzle found along the way.
+pub fn generate(settings: &GeneratorSettings) -> Vec<Puzzle> {
+    let mut rng = rand::thread_rng();
+    let mut seen = HashSet::new();
+    let mut puzzles = Vec::new();
+    for _ in 0..settings.games {
+        let levels = [rng.gen_range(0..=levels::max_level()), rng.gen_range(0..=levels::max_level())];
+        let mut state = GameState::new();
+        while let Some(player) = side_to_move(&state) {
+            if legal_moves(&state).len() <= settings.max_branching {
+                if let Some(p) = find_puzzle(&state, settings.min_moves, settings.max_moves) {
+                    if seen.insert(p.position.clone()) {
+                        puzzles.push(p);
+                    }
+                }
+            }
+            computer_move(&mut state, levels[(player == Cell::Red) as usize]);
+        }
+    }
+    puzzles.sort_by_key(|p| p.rating);
+    puzzles
+}
+
+pub fn puzzles() -> &'static [Puzzle] {
+    PUZZLE_SET.get_or_init(|| serde_json::from_str(PUZZLES).expect("built-in puzzles.json is valid"))
+}
+
+pub fn puzzle(id: &str) -> Option<&'static Puzzle> {
+    puzzles().iter().find(|p| p.id == id)
+}
+
+// Days since the Unix epoch pick the puzzle, so everyone gets the same one on the same day.
+pub fn daily(day: u64) -> Option<&'static Puzzle> {
+    let all = puzzles();
+    if all.is_empty() { return None; }
+    all.get((day % all.len() as u64) as usize)
+}
+
+impl PuzzleSession {
+    pub fn new(puzzle: &Puzzle) -> Result<Self, String> {
+        let state = notation::decode(&puzzle.position)?;
+        Ok(Self {
+            puzzle_id: puzzle.id.clone(),
+            state,
+            player: puzzle.player,
+            moves_left: puzzle.moves,
+            status: PuzzleStatus::Solving,
+            history: Vec::new(),
+        })
+    }
+
+    // Rebuilds a session from the solver's moves, so a client can't hand back a doctored position.
+    pub fn replay(puzzle: &Puzzle, moves: &[(usize, usize)]) -> Result<Self, String> {
+        let mut session = Self::new(puzzle)?;
+        for &(b, c) in moves {
+            let step = session.play(b, c);
+            if !step.ok {
+                return Err(step.error.unwrap_or_default());
+            }
+            session = step.session;
+        }
+        Ok(session)
+    }
+
+    pub fn solver_moves(&self) -> Vec<(usize, usize)> {
+        self.history.iter().step_by(2).copied().collect()
+    }
+
+    // Accepts any move that keeps the forced win, then plays the longest-lasting defence.
+    pub fn play(&self, board_idx: usize, cell_idx: usize) -> PuzzleStep {
+        let reject = |msg: &str| PuzzleStep {
+            ok: false,
+            session: self.clone(),
+            reply: None,
+            error: Some(msg.into()),
+        };
+        if self.status != PuzzleStatus::Solving {
+            return reject("Puzzle is already finished");
+        }
+        if side_to_move(&self.state) != Some(self.player) {
+            return reject("Not the solver's turn");
+        }
+        if !legal_moves(&self.state).contains(&(board_idx, cell_idx)) {
+            return reject("Illegal move");
+        }
+
+        // Sessions come back from the client, so the count can't be trusted to still be positive.
+        let Some(moves_left) = self.moves_left.checked_sub(1) else {
+            return reject("No moves left");
+        };
+
+        let mut session = self.clone();
+        let keeps_win = wins_within(&after(&self.state, (board_idx, cell_idx), self.player), self.player, moves_left);
+        if !keeps_win {
+            session.status = PuzzleStatus::Failed;
+            session.history.push((board_idx, cell_idx));
+            return PuzzleStep { ok: true, session, reply: None, error: None };
+        }
+
+        apply_move(&mut session.state, board_idx, cell_idx, self.player);
+        session.history.push((board_idx, cell_idx));
+        session.moves_left = moves_left;
+        if session.state.status == win_status(self.player) {
+            session.status = PuzzleStatus::Solved;
+            return PuzzleStep { ok: true, session, reply: None, error: None };
+        }
+
+        let defender = opponent(self.player);
+        let reply = best_defence(&session.state, self.player, session.moves_left);
+        if let Some((b, c)) = reply {
+            apply_move(&mut session.state, b, c, defender);
+            session.history.push((b, c));
+        }
+        PuzzleStep { ok: true, session, reply, error: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Blue owns the top-left and top boards and can win the top-right board, and the game, at once.
+    const ONE_MOVER: &str =
+        "bbb.r.r../bbb.rr.../bb..r..../........./r.r....../r......../........./........./......... bb....... b 2";
+
+    #[test]
+    fn an_immediate_win_is_found() {
+        let state = notation::decode(ONE_MOVER).unwrap();
+        assert!(!wins_within(&state, Cell::Blue, 0));
+        assert!(wins_within(&state, Cell::Blue, 1));
+        assert!(!wins_within(&state, Cell::Red, 3));
+        assert_eq!(fastest_win(&state, Cell::Blue, 3), Some(1));
+        assert_eq!(winning_moves(&state, 1), [(2, 2)]);
+
+        let won = after(&state, (2, 2), Cell::Blue);
+        assert!(wins_within(&won, Cell::Blue, 0));
+        assert_eq!(fastest_win(&won, Cell::Red, 3), None);
+    }
+
+    #[test]
+    fn find_puzzle_keeps_to_the_length_asked_for() {
+        let state = notation::decode(ONE_MOVER).unwrap();
+        let p = find_puzzle(&state, 1, 3).unwrap();
+        assert_eq!((p.player, p.moves, p.solution.as_slice()), (Cell::Blue, 1, [(2, 2)].as_slice()));
+        assert!(!p.themes.is_empty());
+        assert!(find_puzzle(&state, 2, 3).is_none());
+        assert!(find_puzzle(&GameState::new(), 1, 2).is_none());
+    }
+
+    #[test]
+    fn shipped_puzzles_hold_up() {
+        for p in puzzles() {
+            let state = notation::decode(&p.position).unwrap();
+            assert_eq!(fastest_win(&state, p.player, p.moves), Some(p.moves), "{}", p.id);
+            assert_eq!(winning_moves(&state, p.moves), [p.solution[0]], "{}", p.id);
+            assert!(!p.themes.is_empty(), "{}", p.id);
+            // Playing the solution line through wins the game.
+            let mut s = state.clone();
+            for &(b, c) in &p.solution {
+                let player = side_to_move(&s).unwrap();
+                apply_move(&mut s, b, c, player);
+            }
+            assert_eq!(s.status, win_status(p.player), "{}", p.id);
+        }
+    }
+}
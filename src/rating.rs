@@ -0,0 +1,311 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::levels;
+use crate::progress::Outcome;
+use crate::{computer_move_with, side_to_move, Cell, GameState, GameStatus, Handicap};
+
+// Glicko-2 (http://www.glicko.net/glicko/glicko2.pdf).
+const SCALE: f64 = 173.7178;
+const TAU: f64 = 0.5;
+const EPSILON: f64 = 0.000001;
+const DEFAULT_RATING: f64 = 1500.0;
+const DEFAULT_RD: f64 = 350.0;
+const DEFAULT_VOLATILITY: f64 = 0.06;
+const MIN_RD: f64 = 30.0;
+const PROVISIONAL_RD: f64 = 110.0;
+const BOT_RD: f64 = 50.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub rd: f64,
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING,
+            rd: DEFAULT_RD,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+impl Rating {
+    pub fn new(rating: f64) -> Self {
+        Self { rating, ..Self::default() }
+    }
+
+    pub fn is_provisional(&self) -> bool {
+        self.rd > PROVISIONAL_RD
+    }
+
+    fn mu(&self) -> f64 {
+        (self.rating - DEFAULT_RATING) / SCALE
+    }
+
+    fn phi(&self) -> f64 {
+        self.rd / SCALE
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct GameResult {
+    pub opponent: Rating,
+    pub score: f64,
+}
+
+impl Outcome {
+    pub fn score(self) -> f64 {
+        match self {
+            Outcome::Win => 1.0,
+            Outcome::Draw => 0.5,
+            Outcome::Loss => 0.0,
+        }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+fn e(mu: f64, mu_j: f64, phi_j: f64) -> f64 {
+    1.0 / (1.0 + (-g(phi_j) * (mu - mu_j)).exp())
+}
+
+pub fn expected_score(player: &Rating, opponent: &Rating) -> f64 {
+    e(player.mu(), opponent.mu(), opponent.phi())
+}
+
+fn new_volatility(sigma: f64, phi: f64, v: f64, delta: f64) -> f64 {
+    let a = (sigma * sigma).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        ex * (delta * delta - phi * phi - v - ex) / (2.0 * (phi * phi + v + ex).powi(2))
+            - (x - a) / (TAU * TAU)
+    };
+
+    let mut big_a = a;
+    let mut big_b = if delta * delta > phi * phi + v {
+        (delta * delta - phi * phi - v).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * TAU) < 0.0 {
+            k += 1.0;
+        }
+        a - k * TAU
+    };
+    let mut f_a = f(big_a);
+    let mut f_b = f(big_b);
+    while (big_b - big_a).abs() > EPSILON {
+        let c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+        let f_c = f(c);
+        if f_c * f_b <= 0.0 {
+            big_a = big_b;
+            f_a = f_b;
+        } else {
+            f_a /= 2.0;
+        }
+        big_b = c;
+        f_b = f_c;
+    }
+    (big_a / 2.0).exp()
+}
+
+// Rates one rating period. With no results the deviation just grows.
+pub fn update(player: &Rating, results: &[GameResult]) -> Rating {
+    let mu = player.mu();
+    let phi = player.phi();
+    let sigma = player.volatility;
+
+    if results.is_empty() {
+        let rd = ((phi * phi + sigma * sigma).sqrt() * SCALE).min(DEFAULT_RD);
+        return Rating { rd, ..*player };
+    }
+
+    let mut v_inv = 0.0;
+    let mut sum = 0.0;
+    for r in results {
+        let (mu_j, phi_j) = (r.opponent.mu(), r.opponent.phi());
+        let ej = e(mu, mu_j, phi_j);
+        v_inv += g(phi_j).powi(2) * ej * (1.0 - ej);
+        sum += g(phi_j) * (r.score - ej);
+    }
+    let v = 1.0 / v_inv;
+    let delta = v * sum;
+
+    let sigma_new = new_volatility(sigma, phi, v, delta);
+    let phi_star = (phi * phi + sigma_new * sigma_new).sqrt();
+    let phi_new = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+    let mu_new = mu + phi_new * phi_new * sum;
+
+    Rating {
+        rating: mu_new * SCALE + DEFAULT_RATING,
+        rd: (phi_new * SCALE).max(MIN_RD),
+        volatility: sigma_new,
+    }
+}
+
+pub fn update_one(player: &Rating, opponent: &Rating, outcome: Outcome) -> Rating {
+    update(player, &[GameResult { opponent: *opponent, score: outcome.score() }])
+}
+
+// Prices a handicap into the opponent's rating, so winning while receiving odds counts for less.
+pub fn handicap_adjusted(opponent: &Rating, handicap: Option<&Handicap>, me: Cell) -> Rating {
+    match handicap {
+        Some(h) if h.player() == me => Rating { rating: opponent.rating - h.rating_value(), ..*opponent },
+        Some(h) => Rating { rating: opponent.rating + h.rating_value(), ..*opponent },
+        None => *opponent,
+    }
+}
+
+pub fn bot_rating(level: u32) -> Rating {
+    Rating {
+        rating: levels::level_def(level).rating.unwrap_or(DEFAULT_RATING),
+        rd: BOT_RD,
+        volatility: DEFAULT_VOLATILITY,
+    }
+}
+
+pub fn personality_rating(personality: &levels::Personality) -> Rating {
+    Rating {
+        rating: personality.def.rating.unwrap_or(DEFAULT_RATING),
+        rd: BOT_RD,
+        volatility: DEFAULT_VOLATILITY,
+    }
+}
+
+// The ladder level whose bot is rated closest to `rating`.
+pub fn nearest_level(rating: f64) -> u32 {
+    (0..=levels::max_level())
+        .min_by(|&a, &b| (bot_rating(a).rating - rating).abs().total_cmp(&(bot_rating(b).rating - rating).abs()))
+        .unwrap_or(0)
+}
+
+pub struct CalibrationSettings {
+    // Rating periods; every pairing plays `games` games in each.
+    pub rounds: u32,
+    pub games: u32,
+    // Each level meets the levels at most this many steps above and below it.
+    pub spread: u32,
+    // Level 0's rating; self-play only fixes the gaps between levels.
+    pub anchor: f64,
+}
+
+impl Default for CalibrationSettings {
+    fn default() -> Self {
+        Self { rounds: 10, games: 10, spread: 3, anchor: 840.0 }
+    }
+}
+
+fn self_play(a: &levels::LevelDef, b: &levels::LevelDef, a_color: Cell) -> GameStatus {
+    let mut state = GameState::new();
+    while let Some(player) = side_to_move(&state) {
+        computer_move_with(&mut state, if player == a_color { a } else { b });
+    }
+    state.status
+}
+
+// `a`'s score in each of `games` games against `b`, with colours alternating.
+fn play_pair(a: &levels::LevelDef, b: &levels::LevelDef, games: u32) -> Vec<f64> {
+    (0..games)
+        .map(|g| {
+            let color = if g % 2 == 0 { Cell::Blue } else { Cell::Red };
+            Outcome::from_status(self_play(a, b, color), color).map_or(0.5, Outcome::score)
+        })
+        .collect()
+}
+
+// Rates `defs` against each other by self-play, one Glicko-2 rating period per round, with colours alternating.
+// Pairings within a round run on their own threads since the deeper searches are slow.
+pub fn calibrate(defs: &[levels::LevelDef], settings: &CalibrationSettings, mut progress: impl FnMut(u32)) -> Vec<Rating> {
+    let n = defs.len();
+    let pairs: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| (i + 1..n.min(i + 1 + settings.spread as usize)).map(move |j| (i, j)))
+        .collect();
+    let mut ratings = vec![Rating::default(); n];
+    for round in 0..settings.rounds {
+        let scores: Vec<Vec<f64>> = std::thread::scope(|s| {
+            let handles: Vec<_> = pairs.iter()
+                .map(|&(i, j)| s.spawn(move || play_pair(&defs[i], &defs[j], settings.games)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut results = vec![Vec::new(); n];
+        for (&(i, j), pair_scores) in pairs.iter().zip(&scores) {
+            for &score in pair_scores {
+                results[i].push(GameResult { opponent: ratings[j], score });
+                results[j].push(GameResult { opponent: ratings[i], score: 1.0 - score });
+            }
+        }
+        ratings = ratings.iter().zip(&results).map(|(r, res)| update(r, res)).collect();
+        progress(round + 1);
+    }
+    let shift = settings.anchor - ratings.first().map_or(DEFAULT_RATING, |r| r.rating);
+    ratings.iter().map(|r| Rating { rating: r.rating + shift, ..*r }).collect()
+}
+
+// Rates each of `defs`, such as the personalities, against every level in turn. The levels keep the
+// ratings given, held as firmly as a player's games against them would hold them.
+pub fn calibrate_against(
+    defs: &[levels::LevelDef],
+    levels: &[(levels::LevelDef, Rating)],
+    settings: &CalibrationSettings,
+    mut progress: impl FnMut(u32),
+) -> Vec<Rating> {
+    let mut ratings = vec![Rating::default(); defs.len()];
+    for round in 0..settings.rounds {
+        let scores: Vec<Vec<Vec<f64>>> = std::thread::scope(|s| {
+            let handles: Vec<Vec<_>> = defs.iter()
+                .map(|def| levels.iter().map(|(level, _)| s.spawn(move || play_pair(def, level, settings.games))).collect())
+                .collect();
+            handles.into_iter().map(|row| row.into_iter().map(|h| h.join().unwrap()).collect()).collect()
+        });
+        ratings = ratings.iter().zip(&scores)
+            .map(|(r, row)| {
+                let results: Vec<GameResult> = levels.iter().zip(row)
+                    .flat_map(|((_, level), scores)| {
+                        let opponent = Rating { rd: BOT_RD, ..*level };
+                        scores.iter().map(move |&score| GameResult { opponent, score })
+                    })
+                    .collect();
+                update(r, &results)
+            })
+            .collect();
+        progress(round + 1);
+    }
+    ratings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rated(rating: f64, rd: f64) -> Rating {
+        Rating { rating, rd, volatility: DEFAULT_VOLATILITY }
+    }
+
+    // The worked example from section 3 of Glickman's paper.
+    #[test]
+    fn matches_the_published_example() {
+        let results = [
+            GameResult { opponent: rated(1400.0, 30.0), score: 1.0 },
+            GameResult { opponent: rated(1550.0, 100.0), score: 0.0 },
+            GameResult { opponent: rated(1700.0, 300.0), score: 0.0 },
+        ];
+        let after = update(&rated(1500.0, 200.0), &results);
+        assert!((after.rating - 1464.06).abs() < 0.05, "rating {}", after.rating);
+        assert!((after.rd - 151.52).abs() < 0.05, "rd {}", after.rd);
+        assert!((after.volatility - 0.05999).abs() < 0.00001, "volatility {}", after.volatility);
+    }
+
+    #[test]
+    fn an_idle_period_only_widens_the_deviation() {
+        let after = update(&rated(1500.0, 200.0), &[]);
+        assert_eq!(after.rating, 1500.0);
+        assert!((after.rd - 200.27).abs() < 0.05, "rd {}", after.rd);
+    }
+}
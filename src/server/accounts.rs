@@ -0,0 +1,201 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    extract::{FromRequestParts, Path, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use super::players::valid_name;
+use super::store::{Account, PlayerRecord, Role, Session};
+use super::{new_id, now, ApiError, ApiResult, AppState};
+
+const SESSION_SECS: u64 = 30 * 86_400;
+const MIN_PASSWORD_LEN: usize = 8;
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordChange {
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct SessionView {
+    pub token: String,
+    pub account: Account,
+}
+
+// The signed-in account, from an `Authorization: Bearer <token>` header.
+pub struct Auth(pub Account);
+
+impl FromRequestParts<AppState> for Auth {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, app: &AppState) -> Result<Self, Self::Rejection> {
+        authenticate(app, &parts.headers).map(Auth)
+    }
+}
+
+fn unauthorized(message: &str) -> ApiError {
+    ApiError::new(StatusCode::UNAUTHORIZED, message)
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")
+}
+
+pub fn authenticate(app: &AppState, headers: &HeaderMap) -> Result<Account, ApiError> {
+    account_for_token(app, bearer(headers).ok_or_else(|| unauthorized("Sign in first"))?)
+}
+
+// Guests may go without a token, but one that's sent has to be valid.
+pub fn optional_account(app: &AppState, headers: &HeaderMap) -> Result<Option<Account>, ApiError> {
+    bearer(headers).map(|token| account_for_token(app, token)).transpose()
+}
+
+// For WebSockets, which browsers open without custom headers; the token comes in the query string instead.
+pub fn account_for_token(app: &AppState, token: &str) -> Result<Account, ApiError> {
+    let session = app.store.session(token)?
+        .filter(|s| s.expires > now())
+        .ok_or_else(|| unauthorized("Session expired, sign in again"))?;
+    app.store.account(&session.username)?.ok_or_else(|| unauthorized("Account no longer exists"))
+}
+
+// Players manage themselves; a household's admin also manages everyone else in it.
+pub fn can_manage(account: &Account, player: &PlayerRecord) -> bool {
+    account.username == player.name
+        || (account.role == Role::Admin && player.household.as_deref() == Some(account.household.as_str()))
+}
+
+pub fn managed_player(app: &AppState, account: &Account, name: &str) -> Result<PlayerRecord, ApiError> {
+    let player = app.store.player(name)?.ok_or_else(|| ApiError::not_found("player"))?;
+    if !can_manage(account, &player) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, format!("You can't manage {}", name)));
+    }
+    Ok(player)
+}
+
+// Argon2 is deliberately slow, so it runs off the async workers.
+pub async fn hash_password(password: String) -> Result<String, ApiError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(ApiError::bad_request(format!("Password must be at least {} characters", MIN_PASSWORD_LEN)));
+    }
+    let hashed = tokio::task::spawn_blocking(move || {
+        let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())?;
+        Argon2::default().hash_password(password.as_bytes(), &salt).map(|h| h.to_string())
+    });
+    let internal = |e: String| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e);
+    hashed.await.map_err(|e| internal(e.to_string()))?.map_err(|e| internal(e.to_string()))
+}
+
+async fn verify_password(password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash).is_ok_and(|h| Argon2::default().verify_password(password.as_bytes(), &h).is_ok())
+    })
+    .await
+    .unwrap_or(false)
+}
+
+fn start_session(app: &AppState, account: Account) -> Result<SessionView, ApiError> {
+    let session = Session {
+        token: format!("{:032x}", rand::random::<u128>()),
+        username: account.username.clone(),
+        expires: now() + SESSION_SECS,
+    };
+    app.store.create_session(&session)?;
+    Ok(SessionView { token: session.token, account })
+}
+
+// Registering starts a new household with this account as its admin. A name stored before accounts
+// existed can be claimed, keeping its progress and records.
+async fn register(
+    State(app): State<AppState>,
+    Json(req): Json<Credentials>,
+) -> Result<(StatusCode, Json<SessionView>), ApiError> {
+    let username = valid_name(&req.username)?;
+    let existing = app.store.player(&username)?;
+    if existing.as_ref().is_some_and(|p| p.household.is_some()) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "That name is taken"));
+    }
+    let account = Account {
+        username: username.clone(),
+        password_hash: hash_password(req.password).await?,
+        role: Role::Admin,
+        household: new_id(),
+        created: now(),
+    };
+    app.store.create_account(&account)
+        .map_err(|_| ApiError::new(StatusCode::CONFLICT, "That name is taken"))?;
+    let mut player = existing.unwrap_or_else(|| PlayerRecord {
+        name: username,
+        progress: Default::default(),
+        created: now(),
+        household: None,
+    });
+    player.household = Some(account.household.clone());
+    app.store.save_player(&player)?;
+    Ok((StatusCode::CREATED, Json(start_session(&app, account)?)))
+}
+
+async fn login(State(app): State<AppState>, Json(req): Json<Credentials>) -> ApiResult<SessionView> {
+    let wrong = || unauthorized("Wrong username or password");
+    let account = app.store.account(req.username.trim())?.ok_or_else(wrong)?;
+    if !verify_password(req.password, account.password_hash.clone()).await {
+        return Err(wrong());
+    }
+    Ok(Json(start_session(&app, account)?))
+}
+
+async fn logout(State(app): State<AppState>, headers: HeaderMap) -> Result<StatusCode, ApiError> {
+    if let Some(token) = bearer(&headers) {
+        app.store.delete_session(token)?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn current_account(Auth(account): Auth) -> Json<Account> {
+    Json(account)
+}
+
+async fn household(State(app): State<AppState>, Auth(account): Auth) -> ApiResult<Vec<PlayerRecord>> {
+    Ok(Json(app.store.household(&account.household)?))
+}
+
+// Also how an admin first gives a child profile its own login.
+async fn set_password(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(name): Path<String>,
+    Json(req): Json<PasswordChange>,
+) -> Result<StatusCode, ApiError> {
+    let player = managed_player(&app, &account, &name)?;
+    let password_hash = hash_password(req.password).await?;
+    let updated = match app.store.account(&name)? {
+        Some(existing) => Account { password_hash, ..existing },
+        None => Account {
+            username: name,
+            password_hash,
+            role: Role::Member,
+            household: player.household.unwrap_or(account.household),
+            created: now(),
+        },
+    };
+    app.store.save_account(&updated)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/accounts", post(register))
+        .route("/api/accounts/{name}/password", put(set_password))
+        .route("/api/sessions", post(login).delete(logout))
+        .route("/api/account", get(current_account))
+        .route("/api/household", get(household))
+}
@@ -0,0 +1,457 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tokio::time::{timeout_at, Instant};
+
+use super::accounts::{account_for_token, Auth};
+use super::games::{commit_game, run_blocking, Game, GameOptions, MoveRequest, Rules};
+use super::players::valid_name;
+use super::rooms::{open_room, seat_color, seat_index, with_room};
+use super::store::{Account, PlayerRecord, Role, Session};
+use super::{new_id, now, ApiError, AppState};
+use crate::progress::Outcome;
+use crate::{legal_moves, notation, side_to_move, Cell, GameState};
+
+const MOVE_TIME: Duration = Duration::from_secs(5);
+const TOKEN_SECS: u64 = 10 * 365 * 86_400;
+
+#[derive(Deserialize)]
+pub struct NewBot {
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct BotToken {
+    pub name: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct BotQuery {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotMessage {
+    // A game against a built-in level; the bot's own ladder level if none is given.
+    Challenge {
+        #[serde(default)]
+        level: Option<u32>,
+        #[serde(default)]
+        color: Option<Cell>,
+        #[serde(default)]
+        rules: Rules,
+    },
+    // A game against the next registered bot that queues for the same rules.
+    Queue {
+        #[serde(default)]
+        rules: Rules,
+    },
+    Leave,
+    Move { board_idx: usize, cell_idx: usize },
+}
+
+// A bot waiting in the shared queue. The bot that pairs with it hands over its socket so the waiting
+// bot's task can run the game, and gets it back afterwards unless it disconnected.
+pub struct WaitingBot {
+    id: String,
+    name: String,
+    rules: Rules,
+    paired: oneshot::Sender<Challenger>,
+}
+
+struct Challenger {
+    name: String,
+    socket: WebSocket,
+    back: oneshot::Sender<Option<WebSocket>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Forfeit {
+    IllegalMove,
+    Timeout,
+    Disconnected,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotEvent {
+    Welcome { name: String, move_ms: u64 },
+    Started { game: String, color: Cell, level: u32 },
+    Queued,
+    // A game against another bot, played in a room that can be watched under the `game` code.
+    Matched { game: String, color: Cell, opponent: String },
+    YourTurn {
+        game: String,
+        state: GameState,
+        notation: String,
+        legal_moves: Vec<(usize, usize)>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        last_move: Option<(usize, usize)>,
+        move_ms: u64,
+    },
+    GameOver {
+        game: String,
+        outcome: Outcome,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        forfeit: Option<Forfeit>,
+        state: GameState,
+    },
+    Error { message: String },
+}
+
+// Engines are players of the admin's household, so their games count towards stats and the leaderboard
+// like anyone else's. The token is only shown here.
+async fn register_bot(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Json(req): Json<NewBot>,
+) -> Result<(StatusCode, Json<BotToken>), ApiError> {
+    if account.role != Role::Admin {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Only the household admin can register bots"));
+    }
+    let player = PlayerRecord {
+        name: valid_name(&req.name)?,
+        progress: Default::default(),
+        created: now(),
+        household: Some(account.household.clone()),
+    };
+    let bot = Account {
+        username: player.name.clone(),
+        password_hash: String::new(),
+        role: Role::Bot,
+        household: account.household,
+        created: now(),
+    };
+    let session = Session {
+        token: format!("{:032x}", rand::random::<u128>()),
+        username: player.name.clone(),
+        expires: now() + TOKEN_SECS,
+    };
+    app.store.create_bot(&player, &bot, &session)?;
+    Ok((StatusCode::CREATED, Json(BotToken { name: player.name, token: session.token })))
+}
+
+async fn bot_socket_handler(
+    State(app): State<AppState>,
+    Query(query): Query<BotQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let account = account_for_token(&app, &query.token)?;
+    if account.role != Role::Bot {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Only registered bots can connect here"));
+    }
+    Ok(ws.on_upgrade(move |socket| bot_socket(socket, app, account.username)))
+}
+
+async fn send_event(socket: &mut WebSocket, event: &BotEvent) -> bool {
+    let text = serde_json::to_string(event).unwrap();
+    socket.send(Message::Text(text.into())).await.is_ok()
+}
+
+// One game at a time: the bot challenges a level or queues for another bot, plays it out, and may then go again.
+async fn bot_socket(mut socket: WebSocket, app: AppState, name: String) {
+    let welcome = BotEvent::Welcome { name: name.clone(), move_ms: MOVE_TIME.as_millis() as u64 };
+    if !send_event(&mut socket, &welcome).await { return; }
+    loop {
+        let text = match socket.recv().await {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+            Some(Ok(_)) => continue,
+        };
+        let message = match serde_json::from_str(&text) {
+            Ok(BotMessage::Challenge { level, color, rules }) => match start_game(&app, &name, level, color, rules).await {
+                Ok(game) => {
+                    if !play_game(&mut socket, &app, game).await { return; }
+                    continue;
+                }
+                Err(e) => e.message,
+            },
+            // Rules are checked here so a pairing never fails to open its room.
+            Ok(BotMessage::Queue { rules }) => match rules.start() {
+                Ok(_) => {
+                    let Some(back) = join_queue(socket, &app, &name, rules).await else { return };
+                    socket = back;
+                    continue;
+                }
+                Err(e) => e.message,
+            },
+            Ok(BotMessage::Leave) => "Not in the queue".to_string(),
+            Ok(BotMessage::Move { .. }) => "No game in progress; send a challenge first".to_string(),
+            Err(e) => e.to_string(),
+        };
+        if !send_event(&mut socket, &BotEvent::Error { message }).await { return; }
+    }
+}
+
+// Pairs with a bot already waiting for the same rules, or waits for one; the socket comes back once
+// the game is over, or None once the bot has gone.
+async fn join_queue(mut socket: WebSocket, app: &AppState, name: &str, rules: Rules) -> Option<WebSocket> {
+    let waiting = {
+        let mut queue = app.bot_queue.lock().unwrap();
+        // Two connections of one bot mustn't be paired with each other.
+        let idx = queue.iter().position(|w| w.name != name && w.rules == rules);
+        idx.map(|idx| queue.remove(idx))
+    };
+    if let Some(waiting) = waiting {
+        let (back, returned) = oneshot::channel();
+        match waiting.paired.send(Challenger { name: name.to_string(), socket, back }) {
+            Ok(()) => return returned.await.ok().flatten(),
+            // It left just as it was picked; this bot waits in its place.
+            Err(challenger) => socket = challenger.socket,
+        }
+    }
+
+    let (paired, mut challenger) = oneshot::channel();
+    let id = new_id();
+    app.bot_queue.lock().unwrap().push(WaitingBot { id: id.clone(), name: name.to_string(), rules: rules.clone(), paired });
+    let mut alive = send_event(&mut socket, &BotEvent::Queued).await;
+    while alive {
+        tokio::select! {
+            found = &mut challenger => {
+                let Ok(mut found) = found else { break };
+                let [mine, theirs] = play_match(app, [(name, &mut socket), (&found.name, &mut found.socket)], rules).await;
+                let _ = found.back.send(theirs.then_some(found.socket));
+                return mine.then_some(socket);
+            }
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        alive = false;
+                        break;
+                    }
+                    Some(Ok(_)) => continue,
+                };
+                let message = match serde_json::from_str(&text) {
+                    Ok(BotMessage::Leave) => break,
+                    Ok(_) => "Waiting for another bot; send leave to stop".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                alive = send_event(&mut socket, &BotEvent::Error { message }).await;
+            }
+        }
+    }
+    app.bot_queue.lock().unwrap().retain(|w| w.id != id);
+    // A bot that paired with this one just as it left gets its socket back to try again.
+    challenger.close();
+    if let Ok(mut found) = challenger.try_recv() {
+        let message = "The other bot left; queue again".to_string();
+        let ok = send_event(&mut found.socket, &BotEvent::Error { message }).await;
+        let _ = found.back.send(ok.then_some(found.socket));
+    }
+    alive.then_some(socket)
+}
+
+// Plays a rated game between two bots in a room of its own, recorded for both like any matchmade game.
+// Returns which of the two are still connected.
+async fn play_match(app: &AppState, bots: [(&str, &mut WebSocket); 2], rules: Rules) -> [bool; 2] {
+    let [(first_name, first), (second_name, second)] = bots;
+    let mut names = [first_name, second_name];
+    let mut sockets = [first, second];
+    let swapped = rand::random();
+    if swapped {
+        names.swap(0, 1);
+        sockets.swap(0, 1);
+    }
+    let opened = open_room(app, rules, None, |room| {
+        room.rated = true;
+        for (idx, name) in names.iter().enumerate() { room.seat_player(idx, Some(name.to_string())); }
+        (room.code.clone(), room.state.clone())
+    });
+    let (code, mut state) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let mut alive = [true; 2];
+            for idx in 0..2 {
+                alive[idx] = send_event(sockets[idx], &BotEvent::Error { message: e.message.clone() }).await;
+            }
+            if swapped { alive.swap(0, 1); }
+            return alive;
+        }
+    };
+
+    let mut forfeit = None;
+    for idx in 0..2 {
+        let matched = BotEvent::Matched { game: code.clone(), color: seat_color(idx), opponent: names[1 - idx].to_string() };
+        if !send_event(sockets[idx], &matched).await {
+            forfeit = Some((idx, Forfeit::Disconnected));
+            break;
+        }
+    }
+    let mut last_move = None;
+    while forfeit.is_none() {
+        let Some(color) = side_to_move(&state) else { break };
+        let idx = seat_index(color);
+        let turn = BotEvent::YourTurn {
+            game: code.clone(),
+            state: state.clone(),
+            notation: notation::encode(&state),
+            legal_moves: legal_moves(&state),
+            last_move,
+            move_ms: MOVE_TIME.as_millis() as u64,
+        };
+        if !send_event(sockets[idx], &turn).await {
+            forfeit = Some((idx, Forfeit::Disconnected));
+            break;
+        }
+        let req = match next_move(sockets[idx], Instant::now() + MOVE_TIME).await {
+            Ok(req) => req,
+            Err(f) => {
+                forfeit = Some((idx, f));
+                break;
+            }
+        };
+        let played = with_room(app, &code, |room| {
+            room.play(color, req.board_idx, req.cell_idx).map(|()| room.state.clone())
+        });
+        match played {
+            Some(Ok(next)) => {
+                state = next;
+                last_move = Some((req.board_idx, req.cell_idx));
+            }
+            _ => forfeit = Some((idx, Forfeit::IllegalMove)),
+        }
+    }
+    if let Some((idx, _)) = forfeit {
+        let resigned = with_room(app, &code, |room| {
+            room.resign(seat_color(idx));
+            room.state.clone()
+        });
+        if let Some(next) = resigned { state = next; }
+    }
+
+    let mut alive = [true; 2];
+    if let Some((idx, Forfeit::Disconnected)) = forfeit { alive[idx] = false; }
+    for idx in 0..2 {
+        if !alive[idx] { continue; }
+        let over = BotEvent::GameOver {
+            game: code.clone(),
+            outcome: Outcome::from_status(state.status, seat_color(idx)).unwrap_or(Outcome::Draw),
+            forfeit: forfeit.map(|(_, f)| f),
+            state: state.clone(),
+        };
+        alive[idx] = send_event(sockets[idx], &over).await;
+    }
+    // Colours were drawn by swapping the pair; the result goes back in the order it came.
+    if swapped { alive.swap(0, 1); }
+    alive
+}
+
+async fn start_game(app: &AppState, name: &str, level: Option<u32>, color: Option<Cell>, rules: Rules) -> Result<Game, ApiError> {
+    let player = app.store.player(name)?.ok_or_else(|| ApiError::not_found("player"))?;
+    let options = GameOptions {
+        player: Some(name.to_string()),
+        level: level.unwrap_or(player.progress.level),
+        color: color.unwrap_or(if rand::random() { Cell::Blue } else { Cell::Red }),
+        rules,
+    };
+    let mut game = run_blocking(move || Game::new(options)).await??;
+    let (before, played) = (game.state.clone(), game.moves.len());
+    save(app, &mut game, &before, played)?;
+    Ok(game)
+}
+
+fn save(app: &AppState, game: &mut Game, before: &GameState, played: usize) -> Result<(), ApiError> {
+    let _guard = app.game_lock.lock().unwrap();
+    commit_game(app, game, before, played)
+}
+
+// Plays `game` to the end, the bot against the computer; false once the bot has gone.
+async fn play_game(socket: &mut WebSocket, app: &AppState, mut game: Game) -> bool {
+    let started = BotEvent::Started { game: game.id.clone(), color: game.color, level: game.level };
+    let mut forfeit = (!send_event(socket, &started).await).then_some(Forfeit::Disconnected);
+    while forfeit.is_none() && game.outcome().is_none() {
+        let turn = BotEvent::YourTurn {
+            game: game.id.clone(),
+            state: game.state.clone(),
+            notation: notation::encode(&game.state),
+            legal_moves: legal_moves(&game.state),
+            last_move: game.moves.last().copied(),
+            move_ms: MOVE_TIME.as_millis() as u64,
+        };
+        if !send_event(socket, &turn).await {
+            forfeit = Some(Forfeit::Disconnected);
+            break;
+        }
+        let req = match next_move(socket, Instant::now() + MOVE_TIME).await {
+            Ok(req) => req,
+            Err(f) => {
+                forfeit = Some(f);
+                break;
+            }
+        };
+        let (before, played) = (game.state.clone(), game.moves.len());
+        // The computer's reply is searched off the async workers; that only fails if the search panicked.
+        let Ok((played_game, result)) = run_blocking(move || {
+            let result = game.play(&req);
+            (game, result)
+        })
+        .await
+        else {
+            return false;
+        };
+        game = played_game;
+        if result.is_err() {
+            forfeit = Some(Forfeit::IllegalMove);
+            break;
+        }
+        if let Err(e) = save(app, &mut game, &before, played) {
+            eprintln!("Failed to save bot game {}: {}", game.id, e.message);
+        }
+    }
+    if forfeit.is_some() {
+        let (before, played) = (game.state.clone(), game.moves.len());
+        game.forfeit();
+        if let Err(e) = save(app, &mut game, &before, played) {
+            eprintln!("Failed to save bot game {}: {}", game.id, e.message);
+        }
+    }
+    if forfeit == Some(Forfeit::Disconnected) { return false; }
+    let over = BotEvent::GameOver {
+        game: game.id.clone(),
+        outcome: game.outcome().unwrap_or(Outcome::Draw),
+        forfeit,
+        state: game.state.clone(),
+    };
+    send_event(socket, &over).await
+}
+
+// The bot's answer, or why it forfeits. Anything other than a move gets an error while the clock keeps running.
+async fn next_move(socket: &mut WebSocket, deadline: Instant) -> Result<MoveRequest, Forfeit> {
+    loop {
+        let msg = timeout_at(deadline, socket.recv()).await.map_err(|_| Forfeit::Timeout)?;
+        let text = match msg {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Err(Forfeit::Disconnected),
+            Some(Ok(_)) => continue,
+        };
+        let message = match serde_json::from_str(&text) {
+            Ok(BotMessage::Move { board_idx, cell_idx }) => return Ok(MoveRequest { board_idx, cell_idx, coach: false }),
+            Ok(BotMessage::Challenge { .. }) | Ok(BotMessage::Queue { .. }) | Ok(BotMessage::Leave) => {
+                "Finish this game first".to_string()
+            }
+            Err(e) => e.to_string(),
+        };
+        if !send_event(socket, &BotEvent::Error { message }).await { return Err(Forfeit::Disconnected); }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/bots", post(register_bot))
+        .route("/api/bots/ws", get(bot_socket_handler))
+}
@@ -0,0 +1,487 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use super::accounts::{can_manage, managed_player, Auth};
+use super::games::Rules;
+use super::notify::{Notice, NoticeEvent};
+use super::rooms::{seat_color, seat_index};
+use super::store::{Account, GameRecord, Opponent};
+use super::{new_id, now, ApiError, ApiResult, AppState};
+use crate::progress::Outcome;
+use crate::{legal_moves, play_move, side_to_move, Cell, GameState, GameStatus};
+
+const DAY_SECS: u64 = 86_400;
+const DEFAULT_MOVE_DAYS: u32 = 3;
+const MAX_MOVE_DAYS: u32 = 30;
+const SWEEP_EVERY: Duration = Duration::from_secs(600);
+
+// How a game ended when it wasn't decided on the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "by", rename_all = "snake_case")]
+pub enum Ending {
+    Timeout { loser: Cell },
+    Resigned { player: Cell },
+    // Turned down, or withdrawn, before it started; nobody won and nothing is recorded.
+    Declined { player: Cell },
+}
+
+// A game played a move at a time over days, kept in the store between moves.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Correspondence {
+    pub id: String,
+    // Blue's and Red's names.
+    pub players: [String; 2],
+    pub rules: Rules,
+    pub move_days: u32,
+    pub state: GameState,
+    pub moves: Vec<(usize, usize)>,
+    // The challenged side, until they accept. No clock runs before then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invited: Option<Cell>,
+    // When the side to move loses on time; cleared once the game is over.
+    pub deadline: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended: Option<Ending>,
+    pub created: u64,
+}
+
+#[derive(Serialize)]
+pub struct CorrespondenceView {
+    #[serde(flatten)]
+    pub game: Correspondence,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_move: Option<String>,
+    pub legal_moves: Vec<(usize, usize)>,
+}
+
+#[derive(Deserialize)]
+pub struct NewCorrespondence {
+    // One of the signed-in account's players; the account itself if left out.
+    #[serde(default)]
+    pub player: Option<String>,
+    pub opponent: String,
+    #[serde(default)]
+    pub color: Option<Cell>,
+    #[serde(default)]
+    pub move_days: Option<u32>,
+    #[serde(default)]
+    pub rules: Rules,
+}
+
+#[derive(Deserialize)]
+pub struct CorrespondenceMove {
+    pub board_idx: usize,
+    pub cell_idx: usize,
+}
+
+impl Correspondence {
+    fn to_move(&self) -> Option<usize> {
+        if self.invited.is_some() || self.ended.is_some() { return None; }
+        side_to_move(&self.state).map(seat_index)
+    }
+
+    // Whoever has to act next: the challenged player until they accept, then the side to move.
+    fn awaited(&self) -> Option<usize> {
+        self.invited.map(seat_index).or_else(|| self.to_move())
+    }
+
+    pub fn view(self) -> CorrespondenceView {
+        let to_move = self.to_move().map(|i| self.players[i].clone());
+        let legal_moves = if to_move.is_some() { legal_moves(&self.state) } else { Vec::new() };
+        CorrespondenceView { game: self, to_move, legal_moves }
+    }
+
+    // Ends the game in favour of the other side.
+    fn forfeit(&mut self, loser: Cell, ending: Ending) {
+        self.state.status = if loser == Cell::Blue { GameStatus::RedWins } else { GameStatus::BlueWins };
+        self.ended = Some(ending);
+        self.deadline = None;
+    }
+
+    // True if the side to move has just lost on time.
+    fn expire(&mut self, at: u64) -> bool {
+        let (Some(deadline), Some(idx)) = (self.deadline, self.to_move()) else { return false };
+        if at < deadline { return false; }
+        let loser = seat_color(idx);
+        self.forfeit(loser, Ending::Timeout { loser });
+        true
+    }
+}
+
+// Tells whoever has to answer or move next, or both players once it's over, and records a finished game for each side.
+fn announce(app: &AppState, game: &Correspondence) -> Result<(), ApiError> {
+    let notice = |idx: usize, event| Notice {
+        player: game.players[idx].clone(),
+        game: game.id.clone(),
+        opponent: game.players[1 - idx].clone(),
+        event,
+    };
+    if let Some(invited) = game.invited {
+        app.notifier.notify(&notice(seat_index(invited), NoticeEvent::Challenge { move_days: game.move_days }));
+        return Ok(());
+    }
+    if let Some(Ending::Declined { player }) = game.ended {
+        app.notifier.notify(&notice(1 - seat_index(player), NoticeEvent::Declined));
+        return Ok(());
+    }
+    if let (Some(idx), Some(deadline)) = (game.to_move(), game.deadline) {
+        app.notifier.notify(&notice(idx, NoticeEvent::YourMove { deadline, last_move: game.moves.last().copied() }));
+        return Ok(());
+    }
+    for idx in 0..2 {
+        let color = seat_color(idx);
+        let Some(outcome) = Outcome::from_status(game.state.status, color) else { continue };
+        app.store.add_record(&GameRecord {
+            id: format!("{}-{}", game.id, idx),
+            player: game.players[idx].clone(),
+            color,
+            opponent: Opponent::Human { name: Some(game.players[1 - idx].clone()) },
+            outcome,
+            moves: game.moves.clone(),
+            plies: game.state.ply,
+            assisted: false,
+            handicap: game.rules.handicap,
+            rating: None,
+            finished: now(),
+        })?;
+        app.notifier.notify(&notice(idx, NoticeEvent::GameOver { outcome, ending: game.ended }));
+    }
+    Ok(())
+}
+
+// Loads a game under the game lock, settling a missed deadline first, and saves it if `f` changed anything.
+// Notices go out after the save, so nobody is told about a move that didn't stick.
+fn with_correspondence<T>(
+    app: &AppState,
+    id: &str,
+    f: impl FnOnce(&mut Correspondence) -> Result<(T, bool), ApiError>,
+) -> Result<T, ApiError> {
+    let _guard = app.game_lock.lock().unwrap();
+    let mut game = app.store.correspondence(id)?.ok_or_else(|| ApiError::not_found("game"))?;
+    if game.expire(now()) {
+        app.store.save_correspondence(&game)?;
+        announce(app, &game)?;
+    }
+    let (result, changed) = f(&mut game)?;
+    if changed {
+        app.store.save_correspondence(&game)?;
+        announce(app, &game)?;
+    }
+    Ok(result)
+}
+
+// The seat in `game` that `account` speaks for, preferring the one that has to act.
+fn seat_for(app: &AppState, account: &Account, game: &Correspondence) -> Result<usize, ApiError> {
+    let first = game.awaited().unwrap_or(0);
+    for idx in [first, 1 - first] {
+        let player = app.store.player(&game.players[idx])?;
+        if player.is_some_and(|p| can_manage(account, &p)) {
+            return Ok(idx);
+        }
+    }
+    Err(ApiError::new(StatusCode::FORBIDDEN, "You aren't playing in this game"))
+}
+
+fn not_started(game: &Correspondence) -> ApiError {
+    let idx = seat_index(game.invited.unwrap_or(Cell::Blue));
+    ApiError::new(StatusCode::CONFLICT, format!("Waiting for {} to accept", game.players[idx]))
+}
+
+async fn create_correspondence(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Json(req): Json<NewCorrespondence>,
+) -> Result<(StatusCode, Json<CorrespondenceView>), ApiError> {
+    let player = managed_player(&app, &account, req.player.as_deref().unwrap_or(&account.username))?;
+    let opponent = app.store.player(&req.opponent)?.ok_or_else(|| ApiError::not_found("opponent"))?;
+    if opponent.name == player.name {
+        return Err(ApiError::bad_request("You can't play yourself"));
+    }
+    let color = req.color.unwrap_or(if rand::random() { Cell::Blue } else { Cell::Red });
+    if color == Cell::Empty {
+        return Err(ApiError::bad_request("Color must be blue or red"));
+    }
+    let move_days = req.move_days.unwrap_or(DEFAULT_MOVE_DAYS);
+    if !(1..=MAX_MOVE_DAYS).contains(&move_days) {
+        return Err(ApiError::bad_request(format!("Moves may take 1 to {} days", MAX_MOVE_DAYS)));
+    }
+    let mut players = [player.name.clone(), opponent.name];
+    if color == Cell::Red { players.swap(0, 1); }
+    let game = Correspondence {
+        id: new_id(),
+        players,
+        state: req.rules.start()?,
+        rules: req.rules,
+        move_days,
+        moves: Vec::new(),
+        invited: Some(seat_color(1 - seat_index(color))),
+        deadline: None,
+        ended: None,
+        created: now(),
+    };
+    let _guard = app.game_lock.lock().unwrap();
+    app.store.save_correspondence(&game)?;
+    announce(&app, &game)?;
+    Ok((StatusCode::CREATED, Json(game.view())))
+}
+
+async fn get_correspondence(State(app): State<AppState>, Path(id): Path<String>) -> ApiResult<CorrespondenceView> {
+    with_correspondence(&app, &id, |g| Ok((Json(g.clone().view()), false)))
+}
+
+async fn make_move(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(id): Path<String>,
+    Json(req): Json<CorrespondenceMove>,
+) -> ApiResult<CorrespondenceView> {
+    with_correspondence(&app, &id, |g| {
+        let idx = seat_for(&app, &account, g)?;
+        if g.invited.is_some() { return Err(not_started(g)); }
+        if g.ended.is_some() { return Err(ApiError::new(StatusCode::CONFLICT, "Game is already over")); }
+        play_move(&mut g.state, seat_color(idx), req.board_idx, req.cell_idx)?;
+        g.moves.push((req.board_idx, req.cell_idx));
+        g.deadline = side_to_move(&g.state).map(|_| now() + g.move_days as u64 * DAY_SECS);
+        Ok((Json(g.clone().view()), true))
+    })
+}
+
+async fn resign(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(id): Path<String>,
+) -> ApiResult<CorrespondenceView> {
+    with_correspondence(&app, &id, |g| {
+        let idx = seat_for(&app, &account, g)?;
+        if g.invited.is_some() { return Err(not_started(g)); }
+        if g.to_move().is_none() {
+            return Err(ApiError::new(StatusCode::CONFLICT, "Game is already over"));
+        }
+        let player = seat_color(idx);
+        g.forfeit(player, Ending::Resigned { player });
+        Ok((Json(g.clone().view()), true))
+    })
+}
+
+// Only the challenged player can accept. The first move's clock starts now.
+async fn accept(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(id): Path<String>,
+) -> ApiResult<CorrespondenceView> {
+    with_correspondence(&app, &id, |g| {
+        let idx = seat_for(&app, &account, g)?;
+        match g.invited {
+            Some(invited) if seat_index(invited) == idx => {}
+            Some(_) => return Err(ApiError::new(StatusCode::FORBIDDEN, "Only the challenged player can accept")),
+            None => return Err(ApiError::new(StatusCode::CONFLICT, "Game has already started")),
+        }
+        g.invited = None;
+        g.deadline = Some(now() + g.move_days as u64 * DAY_SECS);
+        Ok((Json(g.clone().view()), true))
+    })
+}
+
+// The challenged player turns the game down, or the challenger withdraws it, before it starts.
+async fn decline(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(id): Path<String>,
+) -> ApiResult<CorrespondenceView> {
+    with_correspondence(&app, &id, |g| {
+        let idx = seat_for(&app, &account, g)?;
+        if g.invited.is_none() {
+            return Err(ApiError::new(StatusCode::CONFLICT, "Game has already started; resign instead"));
+        }
+        g.invited = None;
+        g.ended = Some(Ending::Declined { player: seat_color(idx) });
+        Ok((Json(g.clone().view()), true))
+    })
+}
+
+// The player's games where it's their move, most urgent first, then challenges waiting on their answer.
+async fn awaiting(State(app): State<AppState>, Path(name): Path<String>) -> ApiResult<Vec<CorrespondenceView>> {
+    app.store.player(&name)?.ok_or_else(|| ApiError::not_found("player"))?;
+    let ids: Vec<String> = app.store.correspondence_of(&name)?.into_iter().map(|g| g.id).collect();
+    let mut waiting = Vec::new();
+    for id in ids {
+        let game = with_correspondence(&app, &id, |g| Ok((g.clone(), false)))?;
+        if game.awaited().is_some_and(|i| game.players[i] == name) {
+            waiting.push(game);
+        }
+    }
+    waiting.sort_by_key(|g| (g.invited.is_some(), g.deadline));
+    Ok(Json(waiting.into_iter().map(Correspondence::view).collect()))
+}
+
+// Settles missed deadlines in the background, so the winner hears about it without either side looking.
+pub async fn sweep_deadlines(app: AppState) {
+    let mut tick = tokio::time::interval(SWEEP_EVERY);
+    loop {
+        tick.tick().await;
+        let overdue = match app.store.overdue_correspondence(now()) {
+            Ok(games) => games,
+            Err(e) => {
+                eprintln!("Failed to check correspondence deadlines: {}", e);
+                continue;
+            }
+        };
+        for game in overdue {
+            if let Err(e) = with_correspondence(&app, &game.id, |_| Ok(((), false))) {
+                eprintln!("Failed to settle game {}: {}", game.id, e.message);
+            }
+        }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/correspondence", post(create_correspondence))
+        .route("/api/correspondence/{id}", get(get_correspondence))
+        .route("/api/correspondence/{id}/moves", post(make_move))
+        .route("/api/correspondence/{id}/resign", post(resign))
+        .route("/api/correspondence/{id}/accept", post(accept))
+        .route("/api/correspondence/{id}/decline", post(decline))
+        .route("/api/players/{name}/awaiting", get(awaiting))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::server::notify::LogNotifier;
+    use crate::server::store::{MemoryStore, PlayerRecord, Role};
+
+    // Each test logs notices to a file of its own, so they can be read back.
+    struct Fixture {
+        app: AppState,
+        log: std::path::PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let log = std::env::temp_dir().join(format!("ttt-notices-{}.log", new_id()));
+            let notifier = LogNotifier::file(&log).unwrap();
+            let app = AppState::new(Arc::new(MemoryStore::new())).with_notifier(Arc::new(notifier));
+            for name in ["alice", "bob"] {
+                let household = Some(name.to_string());
+                let player = PlayerRecord { name: name.into(), progress: Default::default(), created: 1, household };
+                app.store.create_player(&player).unwrap();
+            }
+            Self { app, log }
+        }
+
+        fn notices(&self) -> Vec<Value> {
+            let text = std::fs::read_to_string(&self.log).unwrap_or_default();
+            text.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+        }
+
+        // Alice challenges Bob, playing Blue.
+        async fn challenge(&self) -> String {
+            let req = NewCorrespondence {
+                player: None,
+                opponent: "bob".into(),
+                color: Some(Cell::Blue),
+                move_days: Some(2),
+                rules: Rules::default(),
+            };
+            let created = create_correspondence(State(self.app.clone()), Auth(account("alice")), Json(req)).await;
+            let (_, Json(view)) = created.unwrap();
+            view.game.id
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.log);
+        }
+    }
+
+    fn account(name: &str) -> Account {
+        let household = name.to_string();
+        Account { username: name.into(), password_hash: String::new(), role: Role::Member, household, created: 1 }
+    }
+
+    async fn awaiting_ids(app: &AppState, name: &str) -> Vec<String> {
+        let Json(views) = awaiting(State(app.clone()), Path(name.into())).await.unwrap();
+        views.into_iter().map(|v| v.game.id).collect()
+    }
+
+    #[tokio::test]
+    async fn a_challenge_is_announced_and_accepted() {
+        let f = Fixture::new();
+        let id = f.challenge().await;
+        let notices = f.notices();
+        assert_eq!(notices.len(), 1);
+        assert_eq!((notices[0]["player"].as_str(), notices[0]["event"].as_str()), (Some("bob"), Some("challenge")));
+        assert_eq!(notices[0]["move_days"], 2);
+        assert_eq!(awaiting_ids(&f.app, "bob").await, [id.as_str()]);
+        assert!(awaiting_ids(&f.app, "alice").await.is_empty());
+
+        let refused = accept(State(f.app.clone()), Auth(account("alice")), Path(id.clone())).await.err().unwrap();
+        assert_eq!(refused.status, StatusCode::FORBIDDEN);
+        let Json(view) = accept(State(f.app.clone()), Auth(account("bob")), Path(id.clone())).await.unwrap();
+        assert_eq!(view.to_move.as_deref(), Some("alice"));
+
+        let notices = f.notices();
+        assert_eq!(notices.len(), 2);
+        assert_eq!((notices[1]["player"].as_str(), notices[1]["event"].as_str()), (Some("alice"), Some("your_move")));
+        assert!(notices[1].get("last_move").is_none());
+        assert_eq!(awaiting_ids(&f.app, "alice").await, [id]);
+        assert!(awaiting_ids(&f.app, "bob").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_declined_challenge_tells_the_challenger() {
+        let f = Fixture::new();
+        let id = f.challenge().await;
+        let Json(view) = decline(State(f.app.clone()), Auth(account("bob")), Path(id.clone())).await.unwrap();
+        assert_eq!(view.game.ended, Some(Ending::Declined { player: Cell::Red }));
+
+        let notices = f.notices();
+        assert_eq!(notices.len(), 2);
+        assert_eq!((notices[1]["player"].as_str(), notices[1]["event"].as_str()), (Some("alice"), Some("declined")));
+        assert!(awaiting_ids(&f.app, "bob").await.is_empty());
+        assert!(f.app.store.records("alice").unwrap().is_empty());
+        let again = accept(State(f.app.clone()), Auth(account("bob")), Path(id)).await.err().unwrap();
+        assert_eq!(again.status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn a_missed_deadline_ends_the_game_for_both() {
+        let f = Fixture::new();
+        let id = f.challenge().await;
+        assert!(accept(State(f.app.clone()), Auth(account("bob")), Path(id.clone())).await.is_ok());
+        let mut game = f.app.store.correspondence(&id).unwrap().unwrap();
+        game.deadline = Some(now() - 1);
+        f.app.store.save_correspondence(&game).unwrap();
+
+        // Looking at the game settles it.
+        assert!(awaiting_ids(&f.app, "alice").await.is_empty());
+        let game = f.app.store.correspondence(&id).unwrap().unwrap();
+        assert_eq!(game.ended, Some(Ending::Timeout { loser: Cell::Blue }));
+        assert_eq!(game.deadline, None);
+
+        let notices = f.notices();
+        let over: Vec<_> = notices[2..].iter()
+            .map(|n| (n["player"].as_str(), n["event"].as_str(), n["outcome"].as_str()))
+            .collect();
+        assert_eq!(over, [(Some("alice"), Some("game_over"), Some("loss")), (Some("bob"), Some("game_over"), Some("win"))]);
+        assert_eq!(notices[2]["ending"]["by"], "timeout");
+        assert_eq!(f.app.store.records("bob").unwrap()[0].outcome, Outcome::Win);
+
+        // It's settled once; a second look sends nothing more.
+        assert!(get_correspondence(State(f.app.clone()), Path(id)).await.is_ok());
+        assert_eq!(f.notices().len(), notices.len());
+    }
+}
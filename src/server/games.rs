@@ -0,0 +1,410 @@
+use axum::{
+    body::Bytes,
+    extract::{ws::WebSocketUpgrade, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::accounts::{authenticate, managed_player};
+use super::rooms::{watch_socket, RoomEvent, EVENT_BUFFER};
+use super::store::{GameRecord, Opponent, PlayerRecord};
+use super::{new_id, now, options_body, ApiError, ApiResult, AppState};
+use crate::levels;
+use crate::progress::Outcome;
+use crate::rating;
+use crate::{
+    apply_move, blunder_warning, check_move, computer_move, legal_moves, opponent, ranked_hints,
+    side_to_move, Cell, Explanation, GameState, GameStatus, Handicap, HintStrength, RankedHint,
+};
+
+const DEFAULT_HINTS: usize = 3;
+const MAX_HINTS: usize = 9;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handicap: Option<Handicap>,
+}
+
+impl Rules {
+    // The opening position under these rules, or 400 if the handicap is malformed.
+    pub fn start(&self) -> Result<GameState, ApiError> {
+        Ok(match self.handicap {
+            Some(h) => GameState::with_handicap(h)?,
+            None => GameState::new(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GameOptions {
+    // Games for a stored player are played at the player's level and count towards their rating.
+    pub player: Option<String>,
+    pub level: u32,
+    pub color: Cell,
+    pub rules: Rules,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        Self {
+            player: None,
+            level: 0,
+            color: Cell::Blue,
+            rules: Rules::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Game {
+    pub id: String,
+    #[serde(default)]
+    pub player: Option<String>,
+    pub level: u32,
+    pub color: Cell,
+    pub rules: Rules,
+    pub state: GameState,
+    // Positions before each of the human's moves, for undo.
+    pub undo: Vec<GameState>,
+    pub assisted: bool,
+    #[serde(default)]
+    pub moves: Vec<(usize, usize)>,
+    // Set once the finished game has been written to the player's record.
+    #[serde(default)]
+    pub recorded: bool,
+}
+
+#[derive(Serialize)]
+pub struct GameView {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player: Option<String>,
+    pub level: u32,
+    pub color: Cell,
+    pub rules: Rules,
+    pub state: GameState,
+    pub legal_moves: Vec<(usize, usize)>,
+    pub assisted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<Explanation>,
+}
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    pub board_idx: usize,
+    pub cell_idx: usize,
+    // Ask for a blunder warning instead of playing a move that hands the computer something.
+    #[serde(default)]
+    pub coach: bool,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct HintRequest {
+    pub count: Option<usize>,
+    pub strength: Option<HintStrength>,
+}
+
+impl Game {
+    pub fn new(options: GameOptions) -> Result<Self, ApiError> {
+        if options.color == Cell::Empty {
+            return Err(ApiError::bad_request("Color must be blue or red"));
+        }
+        let state = options.rules.start()?;
+        let mut game = Self {
+            id: new_id(),
+            player: options.player,
+            level: options.level.min(levels::max_level()),
+            color: options.color,
+            rules: options.rules,
+            state,
+            undo: Vec::new(),
+            assisted: false,
+            moves: Vec::new(),
+            recorded: false,
+        };
+        game.computer_turns();
+        Ok(game)
+    }
+
+    // Blue always opens, and handicaps can give the computer several moves in a row.
+    fn computer_turns(&mut self) {
+        let computer = opponent(self.color);
+        while side_to_move(&self.state) == Some(computer) {
+            computer_move(&mut self.state, self.level);
+            let last = if computer == Cell::Blue { self.state.last_blue } else { self.state.last_red };
+            self.moves.extend(last);
+        }
+    }
+
+    pub fn play(&mut self, req: &MoveRequest) -> Result<Option<Explanation>, ApiError> {
+        check_move(&self.state, self.color, req.board_idx, req.cell_idx)?;
+        if req.coach {
+            if let Some(w) = blunder_warning(&self.state, req.board_idx, req.cell_idx, self.color) {
+                return Ok(Some(w));
+            }
+        }
+        self.undo.push(self.state.clone());
+        apply_move(&mut self.state, req.board_idx, req.cell_idx, self.color);
+        self.moves.push((req.board_idx, req.cell_idx));
+        self.computer_turns();
+        Ok(None)
+    }
+
+    // A finished game has been recorded, so its result stands.
+    pub fn take_back(&mut self) -> Result<(), ApiError> {
+        if self.outcome().is_some() {
+            return Err(ApiError::new(StatusCode::CONFLICT, "Game is already over"));
+        }
+        let previous = self.undo.pop().ok_or_else(|| ApiError::new(StatusCode::CONFLICT, "Nothing to undo"))?;
+        self.state = previous;
+        self.moves.truncate(self.state.ply as usize);
+        self.assisted = true;
+        Ok(())
+    }
+
+    pub fn hints(&mut self, req: &HintRequest) -> Result<Vec<RankedHint>, ApiError> {
+        if side_to_move(&self.state) != Some(self.color) {
+            return Err(ApiError::new(StatusCode::CONFLICT, "Not your turn"));
+        }
+        self.assisted = true;
+        let strength = req.strength.unwrap_or(HintStrength::Standard);
+        Ok(ranked_hints(&self.state, req.count.unwrap_or(DEFAULT_HINTS).min(MAX_HINTS), strength))
+    }
+
+    pub fn view(&self, warning: Option<Explanation>) -> GameView {
+        let legal = if side_to_move(&self.state) == Some(self.color) { legal_moves(&self.state) } else { Vec::new() };
+        GameView {
+            id: self.id.clone(),
+            player: self.player.clone(),
+            level: self.level,
+            color: self.color,
+            rules: self.rules.clone(),
+            state: self.state.clone(),
+            legal_moves: legal,
+            assisted: self.assisted,
+            warning,
+        }
+    }
+
+    // The side playing through this game gives it up.
+    pub fn forfeit(&mut self) {
+        self.state.status = if self.color == Cell::Blue { GameStatus::RedWins } else { GameStatus::BlueWins };
+    }
+
+    pub fn snapshot(&self) -> RoomEvent {
+        RoomEvent::State { state: self.state.clone(), players: [true, true], clocks: None }
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        Outcome::from_status(self.state.status, self.color)
+    }
+}
+
+// Counts a finished game for its player. Only a game at the player's own level moves them along the ladder;
+// one at any other level is still rated against that level.
+fn record_result(app: &AppState, game: &mut Game) -> Result<(), ApiError> {
+    let (Some(name), Some(outcome)) = (game.player.clone(), game.outcome()) else { return Ok(()) };
+    if game.recorded { return Ok(()); }
+    let mut rating = None;
+    if let Some(mut player) = app.store.player(&name)? {
+        let handicap = game.rules.handicap.as_ref();
+        let rated = if player.progress.level == game.level {
+            player.progress.record_game(outcome, game.assisted, handicap, game.color);
+            true
+        } else if !game.assisted {
+            player.progress.record_rated_game(outcome, &rating::bot_rating(game.level), handicap, game.color);
+            true
+        } else {
+            false
+        };
+        if rated {
+            app.store.save_player(&player)?;
+            rating = Some(player.progress.rating);
+        }
+    }
+    app.store.add_record(&GameRecord {
+        id: game.id.clone(),
+        player: name,
+        color: game.color,
+        opponent: Opponent::Level { level: game.level },
+        outcome,
+        moves: game.moves.clone(),
+        plies: game.state.ply,
+        assisted: game.assisted,
+        handicap: game.rules.handicap,
+        rating,
+        finished: now(),
+    })?;
+    game.recorded = true;
+    Ok(())
+}
+
+// Anyone may play an anonymous game by its id; a stored player's games need that player's say-so.
+fn authorize(app: &AppState, headers: &HeaderMap, player: Option<&str>) -> Result<Option<PlayerRecord>, ApiError> {
+    let Some(name) = player else { return Ok(None) };
+    managed_player(app, &authenticate(app, headers)?, name).map(Some)
+}
+
+// Tells a game's spectators what changed: each new move in turn, or the whole position after an undo or forfeit.
+fn publish(app: &AppState, before: &GameState, played: usize, game: &Game) {
+    let mut watchers = app.game_events.lock().unwrap();
+    let Some(events) = watchers.get(&game.id) else { return };
+    if events.receiver_count() == 0 {
+        watchers.remove(&game.id);
+        return;
+    }
+    if game.moves.len() < played || (game.moves.len() == played && game.state.status != before.status) {
+        let _ = events.send(game.snapshot());
+        return;
+    }
+    let mut state = before.clone();
+    for &(board_idx, cell_idx) in &game.moves[played..] {
+        let Some(player) = side_to_move(&state) else { break };
+        apply_move(&mut state, board_idx, cell_idx, player);
+        let _ = events.send(RoomEvent::Move { player, board_idx, cell_idx, state: state.clone(), clocks: None });
+    }
+}
+
+// The computer's reply and hint searches can take a while, so `f` runs off the async workers and
+// without the game lock. Its result is only saved if nobody else changed the game in the meantime.
+async fn with_game<T: Send + 'static>(
+    app: &AppState,
+    headers: &HeaderMap,
+    id: &str,
+    f: impl FnOnce(&mut Game) -> Result<T, ApiError> + Send + 'static,
+) -> Result<T, ApiError> {
+    let mut game = app.store.game(id)?.ok_or_else(|| ApiError::not_found("game"))?;
+    authorize(app, headers, game.player.as_deref())?;
+    let (before, played, loaded) = (game.state.clone(), game.moves.len(), revision(&game));
+    let (mut game, result) = run_blocking(move || {
+        let result = f(&mut game);
+        (game, result)
+    })
+    .await?;
+    let result = result?;
+
+    let _guard = app.game_lock.lock().unwrap();
+    let current = app.store.game(id)?.ok_or_else(|| ApiError::not_found("game"))?;
+    if revision(&current) != loaded {
+        return Err(ApiError::new(StatusCode::CONFLICT, "The game changed during this request; try again"));
+    }
+    commit_game(app, &mut game, &before, played)?;
+    Ok(result)
+}
+
+// Everything a request can change about a game, to tell whether it moved on underneath one.
+fn revision(game: &Game) -> (Vec<(usize, usize)>, usize, bool, GameStatus) {
+    (game.moves.clone(), game.undo.len(), game.assisted, game.state.status)
+}
+
+pub(crate) async fn run_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+// Records the game if it has just finished, saves it and tells its spectators. Callers hold the game lock.
+pub(crate) fn commit_game(app: &AppState, game: &mut Game, before: &GameState, played: usize) -> Result<(), ApiError> {
+    record_result(app, game)?;
+    app.store.save_game(game)?;
+    publish(app, before, played, game);
+    Ok(())
+}
+
+async fn create_game(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<GameView>), ApiError> {
+    let mut options: GameOptions = options_body(&body)?;
+    if let Some(player) = authorize(&app, &headers, options.player.as_deref())? {
+        options.level = player.progress.level;
+    }
+    let mut game = run_blocking(move || Game::new(options)).await??;
+    let _guard = app.game_lock.lock().unwrap();
+    record_result(&app, &mut game)?;
+    app.store.save_game(&game)?;
+    let view = game.view(None);
+    Ok((StatusCode::CREATED, Json(view)))
+}
+
+async fn get_game(State(app): State<AppState>, Path(id): Path<String>) -> ApiResult<GameView> {
+    let game = app.store.game(&id)?.ok_or_else(|| ApiError::not_found("game"))?;
+    Ok(Json(game.view(None)))
+}
+
+async fn delete_game(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    with_game(&app, &headers, &id, |_| Ok(())).await?;
+    app.store.delete_game(&id)?;
+    // Dropping the channel disconnects anyone still watching.
+    app.game_events.lock().unwrap().remove(&id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn make_move(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<MoveRequest>,
+) -> ApiResult<GameView> {
+    with_game(&app, &headers, &id, move |g| {
+        let warning = g.play(&req)?;
+        Ok(Json(g.view(warning)))
+    })
+    .await
+}
+
+async fn hint(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ApiResult<Vec<RankedHint>> {
+    let req: HintRequest = options_body(&body)?;
+    with_game(&app, &headers, &id, move |g| g.hints(&req).map(Json)).await
+}
+
+async fn undo(State(app): State<AppState>, headers: HeaderMap, Path(id): Path<String>) -> ApiResult<GameView> {
+    with_game(&app, &headers, &id, |g| {
+        g.take_back()?;
+        Ok(Json(g.view(None)))
+    })
+    .await
+}
+
+// Subscribing under the game lock means no move can land between the snapshot and the first event.
+async fn watch_game(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let (events, snapshot) = {
+        let _guard = app.game_lock.lock().unwrap();
+        let game = app.store.game(&id)?.ok_or_else(|| ApiError::not_found("game"))?;
+        let mut watchers = app.game_events.lock().unwrap();
+        let events = watchers.entry(id.clone()).or_insert_with(|| broadcast::channel(EVENT_BUFFER).0);
+        (events.subscribe(), game.snapshot())
+    };
+    let resync = move || app.store.game(&id).ok().flatten().map(|g| g.snapshot());
+    Ok(ws.on_upgrade(move |socket| watch_socket(socket, events, snapshot, resync)))
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/games", post(create_game))
+        .route("/api/games/{id}", get(get_game).delete(delete_game))
+        .route("/api/games/{id}/moves", post(make_move))
+        .route("/api/games/{id}/hint", post(hint))
+        .route("/api/games/{id}/undo", post(undo))
+        .route("/api/games/{id}/watch", get(watch_game))
+}
@@ -0,0 +1,253 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::Response,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::accounts::account_for_token;
+use super::games::Rules;
+use super::rooms::{open_room, seat_index, with_room, Seat, TimeControl};
+use super::{new_id, ApiError, AppState};
+use crate::levels;
+use crate::progress::PlayerProgress;
+use crate::rating;
+use crate::Cell;
+
+const BASE_WINDOW: f64 = 100.0;
+const WIDEN_PER_SEC: f64 = 10.0;
+const MAX_WINDOW: f64 = 800.0;
+const BOT_OFFER_AFTER: Duration = Duration::from_secs(30);
+const STATUS_TICK: Duration = Duration::from_secs(1);
+
+// Only players who want the same game are ever paired.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub rules: Rules,
+    pub time_control: Option<TimeControl>,
+}
+
+pub struct Ticket {
+    pub id: String,
+    pub name: Option<String>,
+    pub rating: f64,
+    pub preferences: Preferences,
+    pub joined: Instant,
+    // Where to send the seat when someone else's search finds this ticket.
+    matched: mpsc::UnboundedSender<QueueEvent>,
+}
+
+#[derive(Default)]
+pub struct Queue {
+    pub tickets: Vec<Ticket>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueueEvent {
+    Queued { waiting_secs: u64, window: u32, players: usize },
+    BotOffer { level: u32, name: String },
+    Matched {
+        #[serde(flatten)]
+        seat: Seat,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        opponent: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bot: Option<u32>,
+    },
+    Error { message: String },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum QueueMessage {
+    Join(Preferences),
+    AcceptBot,
+    Leave,
+}
+
+#[derive(Deserialize)]
+pub struct QueueQuery {
+    // Signed-in players are matched on their rating; guests count as a new player.
+    pub token: Option<String>,
+}
+
+// How far apart two ratings may be for a ticket that has waited this long.
+fn window(waited: Duration) -> f64 {
+    (BASE_WINDOW + WIDEN_PER_SEC * waited.as_secs_f64()).min(MAX_WINDOW)
+}
+
+impl Ticket {
+    fn accepts(&self, other: &Ticket) -> bool {
+        self.preferences == other.preferences && (self.rating - other.rating).abs() <= window(self.joined.elapsed())
+    }
+}
+
+impl Queue {
+    fn remove(&mut self, id: &str) -> Option<Ticket> {
+        let idx = self.tickets.iter().position(|t| t.id == id)?;
+        Some(self.tickets.remove(idx))
+    }
+
+    // Takes `id` and the closest-rated ticket that both sides would accept out of the queue.
+    fn find_match(&mut self, id: &str) -> Option<(Ticket, Ticket)> {
+        let me = self.tickets.iter().find(|t| t.id == id)?;
+        let other = self.tickets.iter()
+            // One signed-in player searching from two tabs mustn't be paired with themselves.
+            .filter(|t| t.id != id && (t.name.is_none() || t.name != me.name) && me.accepts(t) && t.accepts(me))
+            .min_by(|a, b| (a.rating - me.rating).abs().total_cmp(&(b.rating - me.rating).abs()))?
+            .id.clone();
+        Some((self.remove(id)?, self.remove(&other)?))
+    }
+}
+
+// Seats a matched pair in a new room with colours drawn at random, and tells the other ticket.
+// Should the room fail to open, both searches end with the error.
+fn try_match(app: &AppState, id: &str) -> Option<QueueEvent> {
+    let (me, other) = app.queue.lock().unwrap().find_match(id)?;
+    let opened = open_room(app, me.preferences.rules.clone(), me.preferences.time_control, |room| {
+        let first = if rand::random() { 0 } else { 1 };
+        room.rated = true;
+        (room.seat_player(first, me.name.clone()), room.seat_player(1 - first, other.name.clone()))
+    });
+    let (mine, theirs) = match opened {
+        Ok(seats) => seats,
+        Err(e) => {
+            let _ = other.matched.send(QueueEvent::Error { message: e.message.clone() });
+            return Some(QueueEvent::Error { message: e.message });
+        }
+    };
+    let _ = other.matched.send(QueueEvent::Matched { seat: theirs, opponent: me.name, bot: None });
+    Some(QueueEvent::Matched { seat: mine, opponent: other.name, bot: None })
+}
+
+fn bot_game(app: &AppState, ticket: &Ticket, level: u32) -> QueueEvent {
+    let color = if rand::random() { Cell::Blue } else { Cell::Red };
+    let opened = open_room(app, ticket.preferences.rules.clone(), ticket.preferences.time_control, |room| {
+        room.rated = true;
+        let seat = room.seat_player(seat_index(color), ticket.name.clone());
+        room.seat_bot(color, level);
+        seat
+    });
+    match opened {
+        Ok(seat) => QueueEvent::Matched { seat, opponent: Some(levels::level_def(level).name), bot: Some(level) },
+        Err(e) => QueueEvent::Error { message: e.message },
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, event: &QueueEvent) -> bool {
+    let text = serde_json::to_string(event).unwrap();
+    socket.send(Message::Text(text.into())).await.is_ok()
+}
+
+async fn queue_socket_handler(
+    State(app): State<AppState>,
+    Query(query): Query<QueueQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let (name, progress) = match &query.token {
+        Some(token) => {
+            let account = account_for_token(&app, token)?;
+            let progress = app.store.player(&account.username)?.map(|p| p.progress).unwrap_or_default();
+            (Some(account.username), progress)
+        }
+        None => (None, PlayerProgress::default()),
+    };
+    // A provisional rating says little yet, so the ladder level stands in for it when picking a bot.
+    let bot_level = if progress.rating.is_provisional() { progress.level } else { rating::nearest_level(progress.rating.rating) };
+    Ok(ws.on_upgrade(move |socket| queue_socket(socket, app, name, progress.rating.rating, bot_level)))
+}
+
+async fn queue_socket(mut socket: WebSocket, app: AppState, name: Option<String>, rating: f64, bot_level: u32) {
+    let preferences = loop {
+        match socket.recv().await {
+            Some(Ok(Message::Text(text))) => {
+                let message = match serde_json::from_str(&text) {
+                    // Rules are checked here so a pairing never fails to open its room.
+                    Ok(QueueMessage::Join(p)) => match p.rules.start() {
+                        Ok(_) => break p,
+                        Err(e) => e.message,
+                    },
+                    _ => "Send a join message first".to_string(),
+                };
+                if !send_event(&mut socket, &QueueEvent::Error { message }).await { return; }
+            }
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+            Some(Ok(_)) => {}
+        }
+    };
+    let (tx, mut matched) = mpsc::unbounded_channel();
+    let id = new_id();
+    app.queue.lock().unwrap().tickets.push(Ticket {
+        id: id.clone(),
+        name,
+        rating,
+        preferences,
+        joined: Instant::now(),
+        matched: tx,
+    });
+
+    let mut tick = tokio::time::interval(STATUS_TICK);
+    let mut offered = None;
+    let result = loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                if let Some(event) = try_match(&app, &id) { break Some(event); }
+                let (waited, players) = {
+                    let queue = app.queue.lock().unwrap();
+                    match queue.tickets.iter().find(|t| t.id == id) {
+                        Some(t) => (t.joined.elapsed(), queue.tickets.len()),
+                        // Someone else's search took this ticket; the seat is on its way.
+                        None => continue,
+                    }
+                };
+                let status = QueueEvent::Queued { waiting_secs: waited.as_secs(), window: window(waited) as u32, players };
+                if !send_event(&mut socket, &status).await { break None; }
+                if offered.is_none() && waited >= BOT_OFFER_AFTER {
+                    offered = Some(bot_level);
+                    let offer = QueueEvent::BotOffer { level: bot_level, name: levels::level_def(bot_level).name };
+                    if !send_event(&mut socket, &offer).await { break None; }
+                }
+            }
+            event = matched.recv() => break event,
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(QueueMessage::AcceptBot) => {
+                        let Some(level) = offered else { continue };
+                        // Only if no one has paired with this ticket in the meantime.
+                        let ticket = app.queue.lock().unwrap().remove(&id);
+                        if let Some(ticket) = ticket { break Some(bot_game(&app, &ticket, level)); }
+                    }
+                    Ok(QueueMessage::Leave) => break None,
+                    _ => {}
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break None,
+                Some(Ok(_)) => {}
+            },
+        }
+    };
+    app.queue.lock().unwrap().remove(&id);
+    if let Some(event) = result {
+        send_event(&mut socket, &event).await;
+    } else if let Ok(QueueEvent::Matched { seat, .. }) = matched.try_recv() {
+        // Paired just as this player left: the seat is given up so the opponent isn't kept waiting for nobody.
+        forfeit_seat(&app, &seat);
+    }
+}
+
+// Ends a freshly matched game for a player who left before it began.
+fn forfeit_seat(app: &AppState, seat: &Seat) {
+    with_room(app, &seat.code, |room| room.resign(seat.color));
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/api/matchmaking/ws", get(queue_socket_handler))
+}
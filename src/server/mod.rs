@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::Bytes,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast;
+use tower_http::services::ServeDir;
+
+use crate::levels::{self, LevelDef};
+use crate::puzzle::{self, PuzzleSession, PuzzleStep, Theme};
+use crate::{HandicapError, MoveError};
+use notify::{LogNotifier, Notifier};
+use store::{MemoryStore, Repository, StoreError};
+
+pub mod accounts;
+pub mod bots;
+pub mod correspondence;
+pub mod games;
+pub mod matchmaking;
+pub mod notify;
+pub mod players;
+pub mod rooms;
+pub mod stats;
+pub mod store;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub store: Arc<dyn Repository>,
+    // Saved games are loaded, changed and written back under this lock so concurrent moves can't interleave.
+    pub game_lock: Arc<Mutex<()>>,
+    pub rooms: Arc<Mutex<HashMap<String, rooms::Room>>>,
+    // Spectators of saved games, by game id; a channel is opened when the first one arrives.
+    pub game_events: Arc<Mutex<HashMap<String, broadcast::Sender<rooms::RoomEvent>>>>,
+    pub queue: Arc<Mutex<matchmaking::Queue>>,
+    // Registered bots waiting to play each other.
+    pub bot_queue: Arc<Mutex<Vec<bots::WaitingBot>>>,
+    // Tells correspondence players when it's their move.
+    pub notifier: Arc<dyn Notifier>,
+}
+
+impl AppState {
+    pub fn new(store: Arc<dyn Repository>) -> Self {
+        Self {
+            store,
+            game_lock: Arc::default(),
+            rooms: Arc::default(),
+            game_events: Arc::default(),
+            queue: Arc::default(),
+            bot_queue: Arc::default(),
+            notifier: Arc::new(LogNotifier::stdout()),
+        }
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new(Arc::new(MemoryStore::new()))
+    }
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    pub fn not_found(what: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("Unknown {}", what))
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+}
+
+impl From<MoveError> for ApiError {
+    fn from(e: MoveError) -> Self {
+        let status = match e {
+            MoveError::InvalidIndices | MoveError::IllegalMove => StatusCode::BAD_REQUEST,
+            MoveError::GameOver | MoveError::NotYourTurn => StatusCode::CONFLICT,
+        };
+        Self::new(status, e.to_string())
+    }
+}
+
+impl From<HandicapError> for ApiError {
+    fn from(e: HandicapError) -> Self {
+        Self::bad_request(e.to_string())
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(e: StoreError) -> Self {
+        let status = match e {
+            StoreError::NotFound => StatusCode::NOT_FOUND,
+            StoreError::AlreadyExists => StatusCode::CONFLICT,
+            StoreError::Backend(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+pub(crate) fn new_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+// Options bodies may be left out entirely, with or without a JSON content type.
+pub(crate) fn options_body<T: DeserializeOwned + Default>(body: &Bytes) -> Result<T, ApiError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|e| ApiError::bad_request(e.to_string()))
+}
+
+async fn handle_index() -> Html<&'static str> {
+    Html(include_str!("../../static/index.html"))
+}
+
+async fn handle_levels() -> Json<Vec<LevelDef>> {
+    Json(levels::levels().to_vec())
+}
+
+#[derive(Serialize)]
+struct DailyPuzzle {
+    id: String,
+    rating: u32,
+    moves: u32,
+    themes: Vec<Theme>,
+    session: PuzzleSession,
+}
+
+#[derive(Deserialize)]
+struct PuzzleMoveRequest {
+    session: PuzzleSession,
+    board_idx: usize,
+    cell_idx: usize,
+}
+
+async fn handle_daily_puzzle() -> ApiResult<DailyPuzzle> {
+    let day = now() / 86_400;
+    let p = puzzle::daily(day).ok_or_else(|| ApiError::not_found("puzzle"))?;
+    let session = PuzzleSession::new(p).map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(DailyPuzzle {
+        id: p.id.clone(),
+        rating: p.rating,
+        moves: p.moves,
+        themes: p.themes.clone(),
+        session,
+    }))
+}
+
+// Stateless: the session is rebuilt from the puzzle and the solver's moves before the new move is played.
+// Checking a move searches every defence, so it runs off the async workers.
+async fn handle_puzzle_move(Json(req): Json<PuzzleMoveRequest>) -> ApiResult<PuzzleStep> {
+    let p = puzzle::puzzle(&req.session.puzzle_id).ok_or_else(|| ApiError::not_found("puzzle"))?;
+    let step = games::run_blocking(move || {
+        let session = PuzzleSession::replay(p, &req.session.solver_moves())?;
+        Ok::<_, String>(session.play(req.board_idx, req.cell_idx))
+    })
+    .await?
+    .map_err(ApiError::bad_request)?;
+    Ok(Json(step))
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/", get(handle_index))
+        .route("/api/levels", get(handle_levels))
+        .route("/api/puzzle/daily", get(handle_daily_puzzle))
+        .route("/api/puzzle/move", post(handle_puzzle_move))
+        .merge(accounts::routes())
+        .merge(bots::routes())
+        .merge(correspondence::routes())
+        .merge(games::routes())
+        .merge(matchmaking::routes())
+        .merge(players::routes())
+        .merge(rooms::routes())
+        .merge(stats::routes())
+        .fallback_service(ServeDir::new("static"))
+        .with_state(state)
+}
@@ -0,0 +1,96 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use super::correspondence::Ending;
+use crate::progress::Outcome;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Notice {
+    // Who the notice is for.
+    pub player: String,
+    pub game: String,
+    pub opponent: String,
+    #[serde(flatten)]
+    pub event: NoticeEvent,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NoticeEvent {
+    // The opponent wants to play; nothing starts until this player accepts.
+    Challenge { move_days: u32 },
+    Declined,
+    YourMove {
+        deadline: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        last_move: Option<(usize, usize)>,
+    },
+    GameOver {
+        outcome: Outcome,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ending: Option<Ending>,
+    },
+}
+
+// Reaches players who aren't at the board. Delivery is best effort: a move stands whether or not the
+// opponent heard about it, so failures are logged rather than returned.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, notice: &Notice);
+}
+
+// Writes each notice as a line of JSON, to a file or standard output.
+pub struct LogNotifier {
+    file: Option<Mutex<File>>,
+}
+
+impl LogNotifier {
+    pub fn stdout() -> Self {
+        Self { file: None }
+    }
+
+    pub fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Some(Mutex::new(file)) })
+    }
+}
+
+impl Notifier for LogNotifier {
+    fn notify(&self, notice: &Notice) {
+        let line = serde_json::to_string(notice).unwrap();
+        match &self.file {
+            Some(file) => {
+                if let Err(e) = writeln!(file.lock().unwrap(), "{}", line) {
+                    eprintln!("Failed to write notice: {}", e);
+                }
+            }
+            None => println!("Notice: {}", line),
+        }
+    }
+}
+
+// POSTs each notice as JSON to a URL, off the async workers.
+pub struct WebhookNotifier {
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, notice: &Notice) {
+        let url = self.url.clone();
+        let body = serde_json::to_string(notice).unwrap();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = ureq::post(&url).header("Content-Type", "application/json").send(&body) {
+                eprintln!("Failed to deliver notice to {}: {}", url, e);
+            }
+        });
+    }
+}
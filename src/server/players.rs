@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+
+use super::accounts::{hash_password, managed_player, Auth};
+use super::games::GameView;
+use super::store::{Account, GameRecord, PlayerRecord, Role};
+use super::{now, ApiError, ApiResult, AppState};
+use crate::progress::{Outcome, PlayerProgress};
+
+const MAX_NAME_LEN: usize = 40;
+
+#[derive(Deserialize)]
+pub struct NewPlayer {
+    pub name: String,
+    #[serde(default)]
+    pub progress: Option<LadderSync>,
+    // Gives the new profile its own login; without one only the admin can play it.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+// The ladder position from a browser that played offline. Any other progress fields it sends are ignored.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct LadderSync {
+    pub level: u32,
+    pub history: Vec<Outcome>,
+}
+
+#[derive(Deserialize)]
+pub struct RenameRequest {
+    pub name: String,
+}
+
+pub(crate) fn valid_name(name: &str) -> Result<String, ApiError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(ApiError::bad_request(format!("Name must be 1 to {} characters", MAX_NAME_LEN)));
+    }
+    Ok(name.to_string())
+}
+
+async fn list_players(State(app): State<AppState>) -> ApiResult<Vec<PlayerRecord>> {
+    Ok(Json(app.store.players()?))
+}
+
+// Household admins add child profiles. A browser that already has local progress can bring its ladder level along.
+async fn create_player(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Json(req): Json<NewPlayer>,
+) -> Result<(StatusCode, Json<PlayerRecord>), ApiError> {
+    if account.role != Role::Admin {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Only the household admin can add players"));
+    }
+    let ladder = req.progress.unwrap_or_default();
+    let mut progress = PlayerProgress::default();
+    progress.sync_ladder(ladder.level, ladder.history);
+    let player = PlayerRecord {
+        name: valid_name(&req.name)?,
+        progress,
+        created: now(),
+        household: Some(account.household.clone()),
+    };
+    let password_hash = match req.password {
+        Some(p) => Some(hash_password(p).await?),
+        None => None,
+    };
+    app.store.create_player(&player)?;
+    if let Some(password_hash) = password_hash {
+        app.store.save_account(&Account {
+            username: player.name.clone(),
+            password_hash,
+            role: Role::Member,
+            household: account.household,
+            created: now(),
+        })?;
+    }
+    Ok((StatusCode::CREATED, Json(player)))
+}
+
+async fn get_player(State(app): State<AppState>, Path(name): Path<String>) -> ApiResult<PlayerRecord> {
+    Ok(Json(app.store.player(&name)?.ok_or_else(|| ApiError::not_found("player"))?))
+}
+
+async fn put_progress(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(name): Path<String>,
+    Json(ladder): Json<LadderSync>,
+) -> ApiResult<PlayerRecord> {
+    let mut player = managed_player(&app, &account, &name)?;
+    player.progress.sync_ladder(ladder.level, ladder.history);
+    app.store.save_player(&player)?;
+    Ok(Json(player))
+}
+
+async fn rename_player(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(name): Path<String>,
+    Json(req): Json<RenameRequest>,
+) -> ApiResult<PlayerRecord> {
+    managed_player(&app, &account, &name)?;
+    let new = valid_name(&req.name)?;
+    app.store.rename_player(&name, &new)?;
+    Ok(Json(app.store.player(&new)?.ok_or_else(|| ApiError::not_found("player"))?))
+}
+
+// An admin stays so the rest of the household can still be managed.
+async fn delete_player(
+    State(app): State<AppState>,
+    Auth(account): Auth,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    managed_player(&app, &account, &name)?;
+    if account.username == name && account.role == Role::Admin {
+        return Err(ApiError::new(StatusCode::CONFLICT, "The household admin can't be deleted"));
+    }
+    app.store.delete_player(&name)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn player_records(State(app): State<AppState>, Path(name): Path<String>) -> ApiResult<Vec<GameRecord>> {
+    Ok(Json(app.store.records(&name)?))
+}
+
+async fn player_games(State(app): State<AppState>, Path(name): Path<String>) -> ApiResult<Vec<GameView>> {
+    Ok(Json(app.store.games_of(&name)?.iter().map(|g| g.view(None)).collect()))
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/players", get(list_players).post(create_player))
+        .route("/api/players/{name}", get(get_player).put(put_progress).delete(delete_player))
+        .route("/api/players/{name}/rename", post(rename_player))
+        .route("/api/players/{name}/records", get(player_records))
+        .route("/api/players/{name}/games", get(player_games))
+}
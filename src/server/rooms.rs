@@ -0,0 +1,563 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Bytes,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::accounts::optional_account;
+use super::games::Rules;
+use super::store::{GameRecord, Opponent};
+use super::{new_id, now, options_body, ApiError, ApiResult, AppState};
+use crate::levels;
+use crate::progress::Outcome;
+use crate::rating::{self, Rating};
+use crate::{computer_move, opponent, play_move, side_to_move, Cell, GameState, GameStatus, MoveError};
+
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 6;
+pub const EVENT_BUFFER: usize = 32;
+const CLOCK_TICK: Duration = Duration::from_secs(1);
+// Rooms nobody has moved or joined in this long are abandoned.
+const ROOM_IDLE: Duration = Duration::from_secs(2 * 3600);
+const SWEEP_EVERY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub initial_secs: u32,
+    #[serde(default)]
+    pub increment_secs: u32,
+}
+
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    // The seat whose time is running, and since when.
+    running: Option<(usize, Instant)>,
+}
+
+impl Clock {
+    fn new(control: TimeControl) -> Self {
+        let initial = Duration::from_secs(control.initial_secs as u64);
+        Self { control, remaining: [initial; 2], running: None }
+    }
+
+    fn left(&self, idx: usize) -> Duration {
+        match self.running {
+            Some((i, since)) if i == idx => self.remaining[idx].saturating_sub(since.elapsed()),
+            _ => self.remaining[idx],
+        }
+    }
+
+    pub fn remaining_ms(&self) -> [u64; 2] {
+        [0, 1].map(|i| self.left(i).as_millis() as u64)
+    }
+
+    fn flagged(&self) -> Option<usize> {
+        self.running.map(|(i, _)| i).filter(|&i| self.left(i).is_zero())
+    }
+
+    // Charges the seat that just moved, with its increment, and starts `next`'s time.
+    fn switch(&mut self, next: Option<usize>) {
+        if let Some((idx, _)) = self.running {
+            self.remaining[idx] = self.left(idx) + Duration::from_secs(self.control.increment_secs as u64);
+        }
+        self.running = next.map(|n| (n, Instant::now()));
+    }
+}
+
+pub struct Room {
+    pub code: String,
+    // Unlike the code, never reused; finished games are recorded under it.
+    pub id: String,
+    pub state: GameState,
+    // Seat tokens for Blue and Red; a seat is open until someone joins it.
+    pub seats: [Option<String>; 2],
+    pub events: broadcast::Sender<RoomEvent>,
+    pub rules: Rules,
+    pub clock: Option<Clock>,
+    // A computer opponent in one seat, playing at this level.
+    pub bot: Option<(Cell, u32)>,
+    // Who sits where, when known, for spectators choosing a game.
+    pub names: [Option<String>; 2],
+    // Signed-in players by seat; their finished games are recorded.
+    pub accounts: [Option<String>; 2],
+    // Matchmade games also count towards ratings; games among friends by invite code are only recorded.
+    pub rated: bool,
+    pub moves: Vec<(usize, usize)>,
+    active: Instant,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomEvent {
+    State {
+        state: GameState,
+        players: [bool; 2],
+        #[serde(skip_serializing_if = "Option::is_none")]
+        clocks: Option<[u64; 2]>,
+    },
+    Joined { color: Cell },
+    Move {
+        player: Cell,
+        board_idx: usize,
+        cell_idx: usize,
+        state: GameState,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        clocks: Option<[u64; 2]>,
+    },
+    Timeout { loser: Cell, state: GameState },
+    Resigned { player: Cell, state: GameState },
+    Error { message: String },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Move { board_idx: usize, cell_idx: usize },
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct CreateRoomRequest {
+    pub color: Option<Cell>,
+    pub rules: Rules,
+    pub time_control: Option<TimeControl>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Seat {
+    pub code: String,
+    pub color: Cell,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomSummary {
+    pub code: String,
+    pub names: [Option<String>; 2],
+    pub ply: u32,
+    pub status: GameStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clocks: Option<[u64; 2]>,
+}
+
+#[derive(Deserialize)]
+pub struct SocketQuery {
+    pub token: String,
+}
+
+pub fn seat_index(color: Cell) -> usize {
+    if color == Cell::Red { 1 } else { 0 }
+}
+
+pub fn seat_color(idx: usize) -> Cell {
+    if idx == 1 { Cell::Red } else { Cell::Blue }
+}
+
+fn invite_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LEN).map(|_| *CODE_CHARS.choose(&mut rng).unwrap() as char).collect()
+}
+
+impl Room {
+    pub fn new(code: String, rules: Rules, time_control: Option<TimeControl>) -> Result<Self, ApiError> {
+        let state = rules.start()?;
+        Ok(Self {
+            code,
+            id: new_id(),
+            state,
+            seats: [None, None],
+            events: broadcast::channel(EVENT_BUFFER).0,
+            rules,
+            clock: time_control.map(Clock::new),
+            bot: None,
+            names: [None, None],
+            accounts: [None, None],
+            rated: false,
+            moves: Vec::new(),
+            active: Instant::now(),
+        })
+    }
+
+    // The clock starts once both seats are taken.
+    pub fn take_seat(&mut self, idx: usize) -> Seat {
+        self.active = Instant::now();
+        let token = new_id();
+        self.seats[idx] = Some(token.clone());
+        if self.seats.iter().all(Option::is_some) {
+            let next = side_to_move(&self.state).map(seat_index);
+            if let Some(clock) = &mut self.clock { clock.switch(next); }
+        }
+        Seat { code: self.code.clone(), color: seat_color(idx), token }
+    }
+
+    // Seats a player who may be signed in, so the game counts for them.
+    pub fn seat_player(&mut self, idx: usize, account: Option<String>) -> Seat {
+        if account.is_some() {
+            self.names[idx] = account.clone();
+            self.accounts[idx] = account;
+        }
+        self.take_seat(idx)
+    }
+
+    pub fn is_over(&self) -> bool {
+        side_to_move(&self.state).is_none()
+    }
+
+    // Seats the computer opposite `color`, letting it move first if it has to.
+    pub fn seat_bot(&mut self, color: Cell, level: u32) {
+        let bot = opponent(color);
+        self.bot = Some((bot, level));
+        self.names[seat_index(bot)] = Some(levels::level_def(level).name);
+        self.take_seat(seat_index(bot));
+        self.bot_turns();
+    }
+
+    pub fn color_of(&self, token: &str) -> Option<Cell> {
+        self.seats.iter().position(|s| s.as_deref() == Some(token)).map(seat_color)
+    }
+
+    fn clocks(&self) -> Option<[u64; 2]> {
+        self.clock.as_ref().map(Clock::remaining_ms)
+    }
+
+    pub fn summary(&self) -> RoomSummary {
+        RoomSummary {
+            code: self.code.clone(),
+            names: self.names.clone(),
+            ply: self.state.ply,
+            status: self.state.status,
+            clocks: self.clocks(),
+        }
+    }
+
+    pub fn snapshot(&self) -> RoomEvent {
+        RoomEvent::State {
+            state: self.state.clone(),
+            players: [self.seats[0].is_some(), self.seats[1].is_some()],
+            clocks: self.clocks(),
+        }
+    }
+
+    // Ends the game if the side to move has run out of time; true if it has.
+    pub fn flag(&mut self) -> bool {
+        let Some(idx) = self.clock.as_ref().and_then(Clock::flagged) else { return false };
+        if let Some(clock) = &mut self.clock { clock.switch(None); }
+        let loser = seat_color(idx);
+        self.state.status = if loser == Cell::Blue { GameStatus::RedWins } else { GameStatus::BlueWins };
+        let _ = self.events.send(RoomEvent::Timeout { loser, state: self.state.clone() });
+        true
+    }
+
+    pub fn resign(&mut self, player: Cell) {
+        if side_to_move(&self.state).is_none() { return; }
+        if let Some(clock) = &mut self.clock { clock.switch(None); }
+        self.state.status = if player == Cell::Blue { GameStatus::RedWins } else { GameStatus::BlueWins };
+        let _ = self.events.send(RoomEvent::Resigned { player, state: self.state.clone() });
+    }
+
+    fn moved(&mut self, player: Cell, board_idx: usize, cell_idx: usize) {
+        self.active = Instant::now();
+        self.moves.push((board_idx, cell_idx));
+        let next = side_to_move(&self.state).map(seat_index);
+        if let Some(clock) = &mut self.clock { clock.switch(next); }
+        let _ = self.events.send(RoomEvent::Move {
+            player,
+            board_idx,
+            cell_idx,
+            state: self.state.clone(),
+            clocks: self.clocks(),
+        });
+    }
+
+    fn bot_turns(&mut self) {
+        let Some((bot, level)) = self.bot else { return };
+        while side_to_move(&self.state) == Some(bot) {
+            computer_move(&mut self.state, level);
+            let last = if bot == Cell::Blue { self.state.last_blue } else { self.state.last_red };
+            let Some((b, c)) = last else { break };
+            self.moved(bot, b, c);
+        }
+    }
+
+    // Legality, turn order and the clock are all checked here, whatever the client believes.
+    pub fn play(&mut self, color: Cell, board_idx: usize, cell_idx: usize) -> Result<(), MoveError> {
+        if self.flag() {
+            return Err(MoveError::GameOver);
+        }
+        play_move(&mut self.state, color, board_idx, cell_idx)?;
+        self.moved(color, board_idx, cell_idx);
+        self.bot_turns();
+        Ok(())
+    }
+}
+
+// Runs `f` on an open room. A game it finishes is recorded and the room closed; sockets still get the
+// events already sent before they see the room go.
+pub fn with_room<T>(app: &AppState, code: &str, f: impl FnOnce(&mut Room) -> T) -> Option<T> {
+    let (result, ended) = {
+        let mut rooms = app.rooms.lock().unwrap();
+        let room = rooms.get_mut(code)?;
+        let result = f(room);
+        let ended = if room.is_over() { rooms.remove(code) } else { None };
+        (result, ended)
+    };
+    if let Some(room) = ended { record_room(app, &room); }
+    Some(result)
+}
+
+// Adds the finished game to the records of each signed-in player in it, rating them in a matchmade game.
+fn record_room(app: &AppState, room: &Room) {
+    let _guard = app.game_lock.lock().unwrap();
+    // Both sides are rated against the other's rating from before the game.
+    let ratings = room.accounts.clone().map(|name| {
+        name.and_then(|n| app.store.player(&n).ok().flatten()).map(|p| p.progress.rating)
+    });
+    for idx in 0..2 {
+        if let Err(e) = record_seat(app, room, idx, ratings[1 - idx]) {
+            eprintln!("Failed to record room {}: {}", room.code, e.message);
+        }
+    }
+}
+
+fn record_seat(app: &AppState, room: &Room, idx: usize, opponent_rating: Option<Rating>) -> Result<(), ApiError> {
+    let color = seat_color(idx);
+    let (Some(name), Some(outcome)) = (&room.accounts[idx], Outcome::from_status(room.state.status, color)) else { return Ok(()) };
+    let (opponent, opponent_rating) = match room.bot {
+        Some((bot, level)) if bot != color => (Opponent::Level { level }, rating::bot_rating(level)),
+        // Guests are rated as newcomers, which moves a rating very little.
+        _ => (Opponent::Human { name: room.accounts[1 - idx].clone() }, opponent_rating.unwrap_or_default()),
+    };
+    let mut rating = None;
+    if room.rated {
+        if let Some(mut player) = app.store.player(name)? {
+            player.progress.record_rated_game(outcome, &opponent_rating, room.rules.handicap.as_ref(), color);
+            app.store.save_player(&player)?;
+            rating = Some(player.progress.rating);
+        }
+    }
+    app.store.add_record(&GameRecord {
+        id: format!("{}-{}", room.id, idx),
+        player: name.clone(),
+        color,
+        opponent,
+        outcome,
+        moves: room.moves.clone(),
+        plies: room.state.ply,
+        assisted: false,
+        handicap: room.rules.handicap,
+        rating,
+        finished: now(),
+    })?;
+    Ok(())
+}
+
+// Closes rooms whose game ended without anyone at the board to see it, such as a clock running out,
+// and rooms left idle. Only finished games are recorded.
+pub async fn sweep_rooms(app: AppState) {
+    let mut tick = tokio::time::interval(SWEEP_EVERY);
+    loop {
+        tick.tick().await;
+        let closed: Vec<Room> = {
+            let mut rooms = app.rooms.lock().unwrap();
+            for room in rooms.values_mut() { room.flag(); }
+            let codes: Vec<String> = rooms.values()
+                .filter(|r| r.is_over() || r.active.elapsed() >= ROOM_IDLE)
+                .map(|r| r.code.clone())
+                .collect();
+            codes.iter().filter_map(|c| rooms.remove(c)).collect()
+        };
+        for room in closed.iter().filter(|r| r.is_over()) { record_room(&app, room); }
+    }
+}
+
+// Opens a room under a code no other room is using; `seat` fills it before anyone can see it.
+pub fn open_room<T>(
+    app: &AppState,
+    rules: Rules,
+    time_control: Option<TimeControl>,
+    seat: impl FnOnce(&mut Room) -> T,
+) -> Result<T, ApiError> {
+    let mut rooms = app.rooms.lock().unwrap();
+    let code = loop {
+        let code = invite_code();
+        if !rooms.contains_key(&code) { break code; }
+    };
+    let mut room = Room::new(code.clone(), rules, time_control)?;
+    let seated = seat(&mut room);
+    rooms.insert(code, room);
+    Ok(seated)
+}
+
+// Signed-in players are seated under their name, so the game counts towards their records.
+async fn create_room(State(app): State<AppState>, headers: HeaderMap, body: Bytes) -> ApiResult<Seat> {
+    let account = optional_account(&app, &headers)?.map(|a| a.username);
+    let req: CreateRoomRequest = options_body(&body)?;
+    let color = req.color.unwrap_or(Cell::Blue);
+    if color == Cell::Empty {
+        return Err(ApiError::bad_request("Color must be blue or red"));
+    }
+    Ok(Json(open_room(&app, req.rules, req.time_control, |room| room.seat_player(seat_index(color), account))?))
+}
+
+// Games under way, for spectators to pick from.
+async fn live_rooms(State(app): State<AppState>) -> Json<Vec<RoomSummary>> {
+    let rooms = app.rooms.lock().unwrap();
+    let mut live: Vec<RoomSummary> = rooms.values()
+        .filter(|r| r.seats.iter().all(Option::is_some) && side_to_move(&r.state).is_some())
+        .map(Room::summary)
+        .collect();
+    live.sort_by(|a, b| a.code.cmp(&b.code));
+    Json(live)
+}
+
+async fn join_room(State(app): State<AppState>, headers: HeaderMap, Path(code): Path<String>) -> ApiResult<Seat> {
+    let account = optional_account(&app, &headers)?.map(|a| a.username);
+    let mut rooms = app.rooms.lock().unwrap();
+    let room = rooms.get_mut(&code.to_uppercase()).ok_or_else(|| ApiError::not_found("room"))?;
+    let idx = room.seats.iter().position(Option::is_none)
+        .ok_or_else(|| ApiError::new(StatusCode::CONFLICT, "Room is full"))?;
+    // Both seats under one account would record a win and a loss against itself.
+    if account.is_some() && account == room.accounts[1 - idx] {
+        return Err(ApiError::new(StatusCode::CONFLICT, "You're already playing in this room"));
+    }
+    let seat = room.seat_player(idx, account);
+    let _ = room.events.send(RoomEvent::Joined { color: seat.color });
+    Ok(Json(seat))
+}
+
+async fn room_socket(
+    State(app): State<AppState>,
+    Path(code): Path<String>,
+    Query(query): Query<SocketQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let code = code.to_uppercase();
+    let (color, events, snapshot) = {
+        let rooms = app.rooms.lock().unwrap();
+        let room = rooms.get(&code).ok_or_else(|| ApiError::not_found("room"))?;
+        let color = room.color_of(&query.token)
+            .ok_or_else(|| ApiError::new(StatusCode::FORBIDDEN, "Not a player in this room"))?;
+        (color, room.events.subscribe(), room.snapshot())
+    };
+    Ok(ws.on_upgrade(move |socket| play_socket(socket, app, code, color, events, snapshot)))
+}
+
+async fn watch_room(
+    State(app): State<AppState>,
+    Path(code): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let code = code.to_uppercase();
+    let (events, snapshot) = {
+        let rooms = app.rooms.lock().unwrap();
+        let room = rooms.get(&code).ok_or_else(|| ApiError::not_found("room"))?;
+        (room.events.subscribe(), room.snapshot())
+    };
+    let resync = move || app.rooms.lock().unwrap().get(&code).map(Room::snapshot);
+    Ok(ws.on_upgrade(move |socket| watch_socket(socket, events, snapshot, resync)))
+}
+
+async fn send_event(socket: &mut WebSocket, event: &RoomEvent) -> bool {
+    let text = serde_json::to_string(event).unwrap();
+    socket.send(Message::Text(text.into())).await.is_ok()
+}
+
+fn handle_message(app: &AppState, code: &str, color: Cell, text: &str) -> Result<(), String> {
+    let ClientMessage::Move { board_idx, cell_idx } = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    with_room(app, code, |room| {
+        if room.seats[seat_index(opponent(color))].is_none() {
+            return Err("Waiting for an opponent".into());
+        }
+        room.play(color, board_idx, cell_idx).map_err(|e| e.to_string())
+    })
+    .unwrap_or_else(|| Err("Room is gone".into()))
+}
+
+async fn play_socket(
+    mut socket: WebSocket,
+    app: AppState,
+    code: String,
+    color: Cell,
+    mut events: broadcast::Receiver<RoomEvent>,
+    snapshot: RoomEvent,
+) {
+    if !send_event(&mut socket, &snapshot).await { return; }
+    let mut tick = tokio::time::interval(CLOCK_TICK);
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    if let Err(message) = handle_message(&app, &code, color, &text) {
+                        if !send_event(&mut socket, &RoomEvent::Error { message }).await { break; }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            event = events.recv() => {
+                let event = match event {
+                    Ok(e) => e,
+                    // Fell behind: resynchronise with the full position.
+                    Err(RecvError::Lagged(_)) => match app.rooms.lock().unwrap().get(&code) {
+                        Some(room) => room.snapshot(),
+                        None => break,
+                    },
+                    Err(RecvError::Closed) => break,
+                };
+                if !send_event(&mut socket, &event).await { break; }
+            }
+            // A player who never moves again still loses on time; the flag is broadcast to the whole room.
+            _ = tick.tick() => {
+                with_room(&app, &code, Room::flag);
+            }
+        }
+    }
+}
+
+// Spectators get the position when they connect and every event after it, but can't play.
+pub async fn watch_socket(
+    mut socket: WebSocket,
+    mut events: broadcast::Receiver<RoomEvent>,
+    snapshot: RoomEvent,
+    resync: impl Fn() -> Option<RoomEvent>,
+) {
+    if !send_event(&mut socket, &snapshot).await { return; }
+    loop {
+        let event = tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(_))) => RoomEvent::Error { message: "Spectators can't play moves".into() },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            event = events.recv() => match event {
+                Ok(e) => e,
+                Err(RecvError::Lagged(_)) => match resync() {
+                    Some(e) => e,
+                    None => break,
+                },
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if !send_event(&mut socket, &event).await { break; }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/rooms", get(live_rooms).post(create_room))
+        .route("/api/rooms/{code}/join", post(join_room))
+        .route("/api/rooms/{code}/ws", get(room_socket))
+        .route("/api/rooms/{code}/watch", get(watch_room))
+}
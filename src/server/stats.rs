@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use super::store::{GameRecord, Opponent, PlayerRecord};
+use super::{ApiError, ApiResult, AppState};
+use crate::progress::Outcome;
+use crate::rating::Rating;
+use crate::Cell;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Tally {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Tally {
+    fn add(&mut self, outcome: Outcome) {
+        self.games += 1;
+        match outcome {
+            Outcome::Win => self.wins += 1,
+            Outcome::Draw => self.draws += 1,
+            Outcome::Loss => self.losses += 1,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ColorTally {
+    pub blue: Tally,
+    pub red: Tally,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RatingPoint {
+    pub finished: u64,
+    pub rating: f64,
+    pub rd: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerStats {
+    pub name: String,
+    pub overall: Tally,
+    // Games against the level ladder only.
+    pub by_level: BTreeMap<u32, Tally>,
+    pub by_color: ColorTally,
+    // Positive for wins in a row, negative for losses.
+    pub current_streak: i32,
+    pub best_streak: u32,
+    // As last synced by the player's client, which may have played offline; everything else comes from the server.
+    pub level: u32,
+    pub highest_level: u32,
+    // In plies, over the games whose length is known.
+    pub average_length: Option<f64>,
+    pub rating: Rating,
+    pub provisional: bool,
+    pub rating_history: Vec<RatingPoint>,
+}
+
+// Streaks follow the ladder: only unassisted level games without a handicap count, and a draw ends one.
+// Progress from before records were kept still counts towards the best streak and highest level.
+pub fn player_stats(player: &PlayerRecord, records: &[GameRecord]) -> PlayerStats {
+    let progress = &player.progress;
+    let mut overall = Tally::default();
+    let mut by_level: BTreeMap<u32, Tally> = BTreeMap::new();
+    let mut by_color = ColorTally::default();
+    let mut streak = 0;
+    let mut best_streak = progress.best_streak;
+    let mut highest_level = progress.highest_level;
+    for r in records {
+        overall.add(r.outcome);
+        if r.color == Cell::Red { by_color.red.add(r.outcome) } else { by_color.blue.add(r.outcome) }
+        if let Opponent::Level { level } = r.opponent {
+            by_level.entry(level).or_default().add(r.outcome);
+            highest_level = highest_level.max(level);
+        }
+        let laddered = matches!(r.opponent, Opponent::Level { .. }) && !r.assisted && r.handicap.is_none();
+        if !laddered { continue; }
+        streak = match r.outcome {
+            Outcome::Win => streak.max(0) + 1,
+            Outcome::Loss => streak.min(0) - 1,
+            Outcome::Draw => 0,
+        };
+        best_streak = best_streak.max(streak.max(0) as u32);
+    }
+    let lengths: Vec<u32> = records.iter().map(|r| r.plies).filter(|&p| p > 0).collect();
+    let average_length = (!lengths.is_empty()).then(|| lengths.iter().sum::<u32>() as f64 / lengths.len() as f64);
+    PlayerStats {
+        name: player.name.clone(),
+        overall,
+        by_level,
+        by_color,
+        current_streak: if records.is_empty() { progress.streak } else { streak },
+        best_streak,
+        level: progress.level,
+        highest_level,
+        average_length,
+        rating: progress.rating,
+        provisional: progress.rating.is_provisional(),
+        rating_history: records.iter()
+            .filter_map(|r| r.rating.map(|x| RatingPoint { finished: r.finished, rating: x.rating, rd: x.rd }))
+            .collect(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Rating,
+    Level,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LeaderboardQuery {
+    pub sort: SortBy,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub name: String,
+    pub rating: f64,
+    pub provisional: bool,
+    // Client-reported, so shown but not ranked on; `sort=level` goes by the highest level played on the server.
+    pub level: u32,
+    pub highest_level: u32,
+    pub games: u32,
+}
+
+// Ranks on what the server has seen played. Players who haven't finished a rated game are left off;
+// provisional ratings rank below settled ones.
+pub fn leaderboard(players: &[PlayerRecord], sort: SortBy, limit: usize) -> Vec<LeaderboardEntry> {
+    let mut ranked: Vec<&PlayerRecord> = players.iter().filter(|p| p.progress.games > 0).collect();
+    let by_rating = |a: &&PlayerRecord, b: &&PlayerRecord| {
+        let (a, b) = (&a.progress.rating, &b.progress.rating);
+        a.is_provisional().cmp(&b.is_provisional()).then(b.rating.total_cmp(&a.rating))
+    };
+    match sort {
+        SortBy::Rating => ranked.sort_by(|a, b| by_rating(a, b).then_with(|| a.name.cmp(&b.name))),
+        SortBy::Level => ranked.sort_by(|a, b| {
+            b.progress.highest_level.cmp(&a.progress.highest_level)
+                .then_with(|| by_rating(a, b))
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+    }
+    ranked.into_iter().take(limit).enumerate().map(|(i, p)| LeaderboardEntry {
+        rank: i + 1,
+        name: p.name.clone(),
+        rating: p.progress.rating.rating,
+        provisional: p.progress.rating.is_provisional(),
+        level: p.progress.level,
+        highest_level: p.progress.highest_level,
+        games: p.progress.games,
+    }).collect()
+}
+
+async fn get_stats(State(app): State<AppState>, Path(name): Path<String>) -> ApiResult<PlayerStats> {
+    let player = app.store.player(&name)?.ok_or_else(|| ApiError::not_found("player"))?;
+    Ok(Json(player_stats(&player, &app.store.records(&name)?)))
+}
+
+async fn get_leaderboard(
+    State(app): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+) -> ApiResult<Vec<LeaderboardEntry>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(Json(leaderboard(&app.store.players()?, query.sort, limit)))
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/players/{name}/stats", get(get_stats))
+        .route("/api/leaderboard", get(get_leaderboard))
+}
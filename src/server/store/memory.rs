@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use super::{Account, GameRecord, PlayerRecord, Repository, Session, StoreError, StoreResult};
+use crate::server::correspondence::Correspondence;
+use crate::server::games::Game;
+
+#[derive(Default)]
+struct Data {
+    players: BTreeMap<String, PlayerRecord>,
+    accounts: HashMap<String, Account>,
+    sessions: HashMap<String, Session>,
+    records: Vec<GameRecord>,
+    games: HashMap<String, Game>,
+    correspondence: HashMap<String, Correspondence>,
+}
+
+// Keeps everything in process memory; for tests and throwaway servers.
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<Data>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Repository for MemoryStore {
+    fn player(&self, name: &str) -> StoreResult<Option<PlayerRecord>> {
+        Ok(self.data.lock().unwrap().players.get(name).cloned())
+    }
+
+    fn players(&self) -> StoreResult<Vec<PlayerRecord>> {
+        Ok(self.data.lock().unwrap().players.values().cloned().collect())
+    }
+
+    fn household(&self, household: &str) -> StoreResult<Vec<PlayerRecord>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.players.values().filter(|p| p.household.as_deref() == Some(household)).cloned().collect())
+    }
+
+    fn create_player(&self, player: &PlayerRecord) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        if data.players.contains_key(&player.name) {
+            return Err(StoreError::AlreadyExists);
+        }
+        data.players.insert(player.name.clone(), player.clone());
+        Ok(())
+    }
+
+    fn save_player(&self, player: &PlayerRecord) -> StoreResult<()> {
+        self.data.lock().unwrap().players.insert(player.name.clone(), player.clone());
+        Ok(())
+    }
+
+    fn rename_player(&self, old: &str, new: &str) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        if data.players.contains_key(new) {
+            return Err(StoreError::AlreadyExists);
+        }
+        let mut player = data.players.remove(old).ok_or(StoreError::NotFound)?;
+        player.name = new.to_string();
+        data.players.insert(new.to_string(), player);
+        if let Some(mut account) = data.accounts.remove(old) {
+            account.username = new.to_string();
+            data.accounts.insert(new.to_string(), account);
+        }
+        for s in data.sessions.values_mut().filter(|s| s.username == old) {
+            s.username = new.to_string();
+        }
+        for r in data.records.iter_mut().filter(|r| r.player == old) {
+            r.player = new.to_string();
+        }
+        for g in data.games.values_mut().filter(|g| g.player.as_deref() == Some(old)) {
+            g.player = Some(new.to_string());
+        }
+        for g in data.correspondence.values_mut() {
+            for p in g.players.iter_mut().filter(|p| *p == old) {
+                *p = new.to_string();
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_player(&self, name: &str) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        data.players.remove(name).ok_or(StoreError::NotFound)?;
+        data.accounts.remove(name);
+        data.sessions.retain(|_, s| s.username != name);
+        data.records.retain(|r| r.player != name);
+        data.games.retain(|_, g| g.player.as_deref() != Some(name));
+        data.correspondence.retain(|_, g| !g.players.iter().any(|p| p == name));
+        Ok(())
+    }
+
+    fn account(&self, username: &str) -> StoreResult<Option<Account>> {
+        Ok(self.data.lock().unwrap().accounts.get(username).cloned())
+    }
+
+    fn create_account(&self, account: &Account) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        if data.accounts.contains_key(&account.username) {
+            return Err(StoreError::AlreadyExists);
+        }
+        data.accounts.insert(account.username.clone(), account.clone());
+        Ok(())
+    }
+
+    fn save_account(&self, account: &Account) -> StoreResult<()> {
+        self.data.lock().unwrap().accounts.insert(account.username.clone(), account.clone());
+        Ok(())
+    }
+
+    fn create_bot(&self, player: &PlayerRecord, account: &Account, session: &Session) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        if data.players.contains_key(&player.name) || data.accounts.contains_key(&account.username) {
+            return Err(StoreError::AlreadyExists);
+        }
+        data.players.insert(player.name.clone(), player.clone());
+        data.accounts.insert(account.username.clone(), account.clone());
+        data.sessions.insert(session.token.clone(), session.clone());
+        Ok(())
+    }
+
+    fn create_session(&self, session: &Session) -> StoreResult<()> {
+        self.data.lock().unwrap().sessions.insert(session.token.clone(), session.clone());
+        Ok(())
+    }
+
+    fn session(&self, token: &str) -> StoreResult<Option<Session>> {
+        Ok(self.data.lock().unwrap().sessions.get(token).cloned())
+    }
+
+    fn delete_session(&self, token: &str) -> StoreResult<()> {
+        self.data.lock().unwrap().sessions.remove(token);
+        Ok(())
+    }
+
+    fn add_record(&self, record: &GameRecord) -> StoreResult<()> {
+        self.data.lock().unwrap().records.push(record.clone());
+        Ok(())
+    }
+
+    fn records(&self, player: &str) -> StoreResult<Vec<GameRecord>> {
+        let data = self.data.lock().unwrap();
+        let mut records: Vec<GameRecord> = data.records.iter().filter(|r| r.player == player).cloned().collect();
+        // Stable, so games finished in the same second stay in the order they were added, as in SQLite.
+        records.sort_by_key(|r| r.finished);
+        Ok(records)
+    }
+
+    fn game(&self, id: &str) -> StoreResult<Option<Game>> {
+        Ok(self.data.lock().unwrap().games.get(id).cloned())
+    }
+
+    fn save_game(&self, game: &Game) -> StoreResult<()> {
+        self.data.lock().unwrap().games.insert(game.id.clone(), game.clone());
+        Ok(())
+    }
+
+    fn delete_game(&self, id: &str) -> StoreResult<()> {
+        self.data.lock().unwrap().games.remove(id);
+        Ok(())
+    }
+
+    fn games_of(&self, player: &str) -> StoreResult<Vec<Game>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.games.values().filter(|g| g.player.as_deref() == Some(player)).cloned().collect())
+    }
+
+    fn correspondence(&self, id: &str) -> StoreResult<Option<Correspondence>> {
+        Ok(self.data.lock().unwrap().correspondence.get(id).cloned())
+    }
+
+    fn save_correspondence(&self, game: &Correspondence) -> StoreResult<()> {
+        self.data.lock().unwrap().correspondence.insert(game.id.clone(), game.clone());
+        Ok(())
+    }
+
+    fn correspondence_of(&self, player: &str) -> StoreResult<Vec<Correspondence>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.correspondence.values().filter(|g| g.players.iter().any(|p| p == player)).cloned().collect())
+    }
+
+    fn overdue_correspondence(&self, at: u64) -> StoreResult<Vec<Correspondence>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.correspondence.values().filter(|g| g.deadline.is_some_and(|d| d <= at)).cloned().collect())
+    }
+}
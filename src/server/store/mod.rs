@@ -0,0 +1,162 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::correspondence::Correspondence;
+use super::games::Game;
+use crate::progress::{Outcome, PlayerProgress};
+use crate::rating::Rating;
+use crate::{Cell, Handicap};
+
+mod memory;
+mod sqlite;
+
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+
+#[derive(Debug)]
+pub enum StoreError {
+    NotFound,
+    AlreadyExists,
+    Backend(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::NotFound => f.write_str("Not found"),
+            StoreError::AlreadyExists => f.write_str("Already exists"),
+            StoreError::Backend(e) => write!(f, "Storage error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Backend(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Backend(e.to_string())
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub name: String,
+    #[serde(default)]
+    pub progress: PlayerProgress,
+    #[serde(default)]
+    pub created: u64,
+    // Players made before accounts existed belong to no household until someone registers their name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub household: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // Registered the household and manages its other profiles.
+    Admin,
+    Member,
+    // An engine playing through the bot socket; it has a token but no password.
+    Bot,
+}
+
+// Login details for the player of the same name.
+#[derive(Clone, Debug, Serialize)]
+pub struct Account {
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub role: Role,
+    pub household: String,
+    pub created: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub token: String,
+    pub username: String,
+    pub expires: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Opponent {
+    Level { level: u32 },
+    Personality { id: String },
+    Human {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+// A finished game, from `player`'s side of the board.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub id: String,
+    pub player: String,
+    pub color: Cell,
+    pub opponent: Opponent,
+    pub outcome: Outcome,
+    // Moves are only known for games played on the server; `plies` is kept for every game.
+    #[serde(default)]
+    pub moves: Vec<(usize, usize)>,
+    #[serde(default)]
+    pub plies: u32,
+    #[serde(default)]
+    pub assisted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handicap: Option<Handicap>,
+    // The player's rating once this game was counted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Rating>,
+    pub finished: u64,
+}
+
+pub trait Repository: Send + Sync {
+    fn player(&self, name: &str) -> StoreResult<Option<PlayerRecord>>;
+    fn players(&self) -> StoreResult<Vec<PlayerRecord>>;
+    fn household(&self, household: &str) -> StoreResult<Vec<PlayerRecord>>;
+    // Fails with `AlreadyExists` if the name is taken.
+    fn create_player(&self, player: &PlayerRecord) -> StoreResult<()>;
+    fn save_player(&self, player: &PlayerRecord) -> StoreResult<()>;
+    // Carries the player's account, sessions, game records, saved games and correspondence games over to the new name.
+    fn rename_player(&self, old: &str, new: &str) -> StoreResult<()>;
+    // Removes the player with their account, sessions, game records, saved games and correspondence games.
+    fn delete_player(&self, name: &str) -> StoreResult<()>;
+
+    fn account(&self, username: &str) -> StoreResult<Option<Account>>;
+    // Fails with `AlreadyExists` if the username is taken.
+    fn create_account(&self, account: &Account) -> StoreResult<()>;
+    fn save_account(&self, account: &Account) -> StoreResult<()>;
+    // A bot's player, account and session all at once, or none of them; `AlreadyExists` if the name is taken.
+    fn create_bot(&self, player: &PlayerRecord, account: &Account, session: &Session) -> StoreResult<()>;
+
+    fn create_session(&self, session: &Session) -> StoreResult<()>;
+    fn session(&self, token: &str) -> StoreResult<Option<Session>>;
+    fn delete_session(&self, token: &str) -> StoreResult<()>;
+
+    fn add_record(&self, record: &GameRecord) -> StoreResult<()>;
+    // Oldest first.
+    fn records(&self, player: &str) -> StoreResult<Vec<GameRecord>>;
+
+    fn game(&self, id: &str) -> StoreResult<Option<Game>>;
+    fn save_game(&self, game: &Game) -> StoreResult<()>;
+    fn delete_game(&self, id: &str) -> StoreResult<()>;
+    fn games_of(&self, player: &str) -> StoreResult<Vec<Game>>;
+
+    fn correspondence(&self, id: &str) -> StoreResult<Option<Correspondence>>;
+    fn save_correspondence(&self, game: &Correspondence) -> StoreResult<()>;
+    // Finished or not, on either side of the board.
+    fn correspondence_of(&self, player: &str) -> StoreResult<Vec<Correspondence>>;
+    // Unfinished games whose side to move has run past its deadline by `at`.
+    fn overdue_correspondence(&self, at: u64) -> StoreResult<Vec<Correspondence>>;
+}
@@ -0,0 +1,386 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{Account, GameRecord, PlayerRecord, Repository, Role, Session, StoreError, StoreResult};
+use crate::server::correspondence::Correspondence;
+use crate::server::games::Game;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS players (
+    name TEXT PRIMARY KEY,
+    created INTEGER NOT NULL,
+    progress TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS game_records (
+    id TEXT PRIMARY KEY,
+    player TEXT NOT NULL,
+    finished INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS game_records_player ON game_records (player, finished);
+CREATE TABLE IF NOT EXISTS saved_games (
+    id TEXT PRIMARY KEY,
+    player TEXT,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS saved_games_player ON saved_games (player);
+CREATE TABLE IF NOT EXISTS accounts (
+    username TEXT PRIMARY KEY,
+    password_hash TEXT NOT NULL,
+    role TEXT NOT NULL,
+    household TEXT NOT NULL,
+    created INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS sessions (
+    token TEXT PRIMARY KEY,
+    username TEXT NOT NULL,
+    expires INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS correspondence (
+    id TEXT PRIMARY KEY,
+    blue TEXT NOT NULL,
+    red TEXT NOT NULL,
+    deadline INTEGER,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS correspondence_blue ON correspondence (blue);
+CREATE INDEX IF NOT EXISTS correspondence_red ON correspondence (red);
+CREATE INDEX IF NOT EXISTS correspondence_deadline ON correspondence (deadline);
+";
+
+// Columns added after a table first shipped, applied to databases that predate them.
+const MIGRATIONS: &[(&str, &str, &str)] = &[
+    ("players", "household", "ALTER TABLE players ADD COLUMN household TEXT"),
+];
+
+// Records and games are stored as JSON next to the columns they are looked up by.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> StoreResult<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> StoreResult<Self> {
+        conn.execute_batch(SCHEMA)?;
+        for (table, column, sql) in MIGRATIONS {
+            if conn.prepare(&format!("SELECT {} FROM {} LIMIT 0", column, table)).is_err() {
+                conn.execute_batch(sql)?;
+            }
+        }
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+}
+
+const PLAYER_COLUMNS: &str = "SELECT name, created, progress, household FROM players";
+
+type PlayerRow = (String, u64, String, Option<String>);
+
+fn player_row(row: &rusqlite::Row) -> rusqlite::Result<PlayerRow> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn to_player((name, created, progress, household): PlayerRow) -> StoreResult<PlayerRecord> {
+    Ok(PlayerRecord { name, created, progress: serde_json::from_str(&progress)?, household })
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Admin => "admin",
+        Role::Member => "member",
+        Role::Bot => "bot",
+    }
+}
+
+fn account_row(row: &rusqlite::Row) -> rusqlite::Result<Account> {
+    let role: String = row.get(2)?;
+    Ok(Account {
+        username: row.get(0)?,
+        password_hash: row.get(1)?,
+        role: match role.as_str() {
+            "admin" => Role::Admin,
+            "bot" => Role::Bot,
+            _ => Role::Member,
+        },
+        household: row.get(3)?,
+        created: row.get(4)?,
+    })
+}
+
+impl Repository for SqliteStore {
+    fn player(&self, name: &str) -> StoreResult<Option<PlayerRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let row = conn
+            .query_row(&format!("{} WHERE name = ?1", PLAYER_COLUMNS), [name], player_row)
+            .optional()?;
+        row.map(to_player).transpose()
+    }
+
+    fn players(&self) -> StoreResult<Vec<PlayerRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("{} ORDER BY name", PLAYER_COLUMNS))?;
+        let rows = stmt.query_map([], player_row)?.collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter().map(to_player).collect()
+    }
+
+    fn household(&self, household: &str) -> StoreResult<Vec<PlayerRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("{} WHERE household = ?1 ORDER BY name", PLAYER_COLUMNS))?;
+        let rows = stmt.query_map([household], player_row)?.collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter().map(to_player).collect()
+    }
+
+    fn create_player(&self, player: &PlayerRecord) -> StoreResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO players (name, created, progress, household) VALUES (?1, ?2, ?3, ?4)",
+            params![player.name, player.created, serde_json::to_string(&player.progress)?, player.household],
+        )?;
+        if inserted == 0 {
+            return Err(StoreError::AlreadyExists);
+        }
+        Ok(())
+    }
+
+    fn save_player(&self, player: &PlayerRecord) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO players (name, created, progress, household) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (name) DO UPDATE SET progress = excluded.progress, household = excluded.household",
+            params![player.name, player.created, serde_json::to_string(&player.progress)?, player.household],
+        )?;
+        Ok(())
+    }
+
+    fn rename_player(&self, old: &str, new: &str) -> StoreResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let taken: bool = tx.query_row("SELECT EXISTS (SELECT 1 FROM players WHERE name = ?1)", [new], |r| r.get(0))?;
+        if taken {
+            return Err(StoreError::AlreadyExists);
+        }
+        if tx.execute("UPDATE players SET name = ?2 WHERE name = ?1", [old, new])? == 0 {
+            return Err(StoreError::NotFound);
+        }
+        tx.execute("UPDATE accounts SET username = ?2 WHERE username = ?1", [old, new])?;
+        tx.execute("UPDATE sessions SET username = ?2 WHERE username = ?1", [old, new])?;
+        // The player's name also lives inside the JSON, so those rows are rewritten rather than just relabelled.
+        let records = {
+            let mut stmt = tx.prepare("SELECT data FROM game_records WHERE player = ?1")?;
+            let rows = stmt.query_map([old], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        for data in records {
+            let mut record: GameRecord = serde_json::from_str(&data)?;
+            record.player = new.to_string();
+            tx.execute(
+                "UPDATE game_records SET player = ?2, data = ?3 WHERE id = ?1",
+                params![record.id, new, serde_json::to_string(&record)?],
+            )?;
+        }
+        let games = {
+            let mut stmt = tx.prepare("SELECT data FROM saved_games WHERE player = ?1")?;
+            let rows = stmt.query_map([old], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        for data in games {
+            let mut game: Game = serde_json::from_str(&data)?;
+            game.player = Some(new.to_string());
+            tx.execute(
+                "UPDATE saved_games SET player = ?2, data = ?3 WHERE id = ?1",
+                params![game.id, new, serde_json::to_string(&game)?],
+            )?;
+        }
+        let correspondence = {
+            let mut stmt = tx.prepare("SELECT data FROM correspondence WHERE blue = ?1 OR red = ?1")?;
+            let rows = stmt.query_map([old], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        for data in correspondence {
+            let mut game: Correspondence = serde_json::from_str(&data)?;
+            for p in game.players.iter_mut().filter(|p| *p == old) {
+                *p = new.to_string();
+            }
+            tx.execute(
+                "UPDATE correspondence SET blue = ?2, red = ?3, data = ?4 WHERE id = ?1",
+                params![game.id, game.players[0], game.players[1], serde_json::to_string(&game)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_player(&self, name: &str) -> StoreResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        if tx.execute("DELETE FROM players WHERE name = ?1", [name])? == 0 {
+            return Err(StoreError::NotFound);
+        }
+        tx.execute("DELETE FROM accounts WHERE username = ?1", [name])?;
+        tx.execute("DELETE FROM sessions WHERE username = ?1", [name])?;
+        tx.execute("DELETE FROM game_records WHERE player = ?1", [name])?;
+        tx.execute("DELETE FROM saved_games WHERE player = ?1", [name])?;
+        tx.execute("DELETE FROM correspondence WHERE blue = ?1 OR red = ?1", [name])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn account(&self, username: &str) -> StoreResult<Option<Account>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT username, password_hash, role, household, created FROM accounts WHERE username = ?1",
+                [username],
+                account_row,
+            )
+            .optional()?)
+    }
+
+    fn create_account(&self, account: &Account) -> StoreResult<()> {
+        let inserted = self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO accounts (username, password_hash, role, household, created) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![account.username, account.password_hash, role_name(account.role), account.household, account.created],
+        )?;
+        if inserted == 0 {
+            return Err(StoreError::AlreadyExists);
+        }
+        Ok(())
+    }
+
+    fn save_account(&self, account: &Account) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO accounts (username, password_hash, role, household, created) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (username) DO UPDATE SET
+                password_hash = excluded.password_hash, role = excluded.role, household = excluded.household",
+            params![account.username, account.password_hash, role_name(account.role), account.household, account.created],
+        )?;
+        Ok(())
+    }
+
+    fn create_bot(&self, player: &PlayerRecord, account: &Account, session: &Session) -> StoreResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO players (name, created, progress, household) VALUES (?1, ?2, ?3, ?4)",
+            params![player.name, player.created, serde_json::to_string(&player.progress)?, player.household],
+        )? + tx.execute(
+            "INSERT OR IGNORE INTO accounts (username, password_hash, role, household, created) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![account.username, account.password_hash, role_name(account.role), account.household, account.created],
+        )?;
+        // Dropping the transaction rolls back whichever row did go in.
+        if inserted < 2 {
+            return Err(StoreError::AlreadyExists);
+        }
+        tx.execute(
+            "INSERT INTO sessions (token, username, expires) VALUES (?1, ?2, ?3)",
+            params![session.token, session.username, session.expires],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn create_session(&self, session: &Session) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO sessions (token, username, expires) VALUES (?1, ?2, ?3)",
+            params![session.token, session.username, session.expires],
+        )?;
+        Ok(())
+    }
+
+    fn session(&self, token: &str) -> StoreResult<Option<Session>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row("SELECT token, username, expires FROM sessions WHERE token = ?1", [token], |r| {
+                Ok(Session { token: r.get(0)?, username: r.get(1)?, expires: r.get(2)? })
+            })
+            .optional()?)
+    }
+
+    fn delete_session(&self, token: &str) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM sessions WHERE token = ?1", [token])?;
+        Ok(())
+    }
+
+    fn add_record(&self, record: &GameRecord) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO game_records (id, player, finished, data) VALUES (?1, ?2, ?3, ?4)",
+            params![record.id, record.player, record.finished, serde_json::to_string(record)?],
+        )?;
+        Ok(())
+    }
+
+    fn records(&self, player: &str) -> StoreResult<Vec<GameRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM game_records WHERE player = ?1 ORDER BY finished, rowid")?;
+        let rows = stmt.query_map([player], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        rows.iter().map(|d| Ok(serde_json::from_str(d)?)).collect()
+    }
+
+    fn game(&self, id: &str) -> StoreResult<Option<Game>> {
+        let conn = self.conn.lock().unwrap();
+        let data: Option<String> = conn
+            .query_row("SELECT data FROM saved_games WHERE id = ?1", [id], |r| r.get(0))
+            .optional()?;
+        Ok(data.map(|d| serde_json::from_str(&d)).transpose()?)
+    }
+
+    fn save_game(&self, game: &Game) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO saved_games (id, player, data) VALUES (?1, ?2, ?3)
+             ON CONFLICT (id) DO UPDATE SET player = excluded.player, data = excluded.data",
+            params![game.id, game.player, serde_json::to_string(game)?],
+        )?;
+        Ok(())
+    }
+
+    fn delete_game(&self, id: &str) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM saved_games WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    fn games_of(&self, player: &str) -> StoreResult<Vec<Game>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM saved_games WHERE player = ?1")?;
+        let rows = stmt.query_map([player], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        rows.iter().map(|d| Ok(serde_json::from_str(d)?)).collect()
+    }
+
+    fn correspondence(&self, id: &str) -> StoreResult<Option<Correspondence>> {
+        let conn = self.conn.lock().unwrap();
+        let data: Option<String> = conn
+            .query_row("SELECT data FROM correspondence WHERE id = ?1", [id], |r| r.get(0))
+            .optional()?;
+        Ok(data.map(|d| serde_json::from_str(&d)).transpose()?)
+    }
+
+    fn save_correspondence(&self, game: &Correspondence) -> StoreResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO correspondence (id, blue, red, deadline, data) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (id) DO UPDATE SET
+                blue = excluded.blue, red = excluded.red, deadline = excluded.deadline, data = excluded.data",
+            params![game.id, game.players[0], game.players[1], game.deadline, serde_json::to_string(game)?],
+        )?;
+        Ok(())
+    }
+
+    fn correspondence_of(&self, player: &str) -> StoreResult<Vec<Correspondence>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM correspondence WHERE blue = ?1 OR red = ?1")?;
+        let rows = stmt.query_map([player], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        rows.iter().map(|d| Ok(serde_json::from_str(d)?)).collect()
+    }
+
+    fn overdue_correspondence(&self, at: u64) -> StoreResult<Vec<Correspondence>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM correspondence WHERE deadline <= ?1")?;
+        let rows = stmt.query_map([at], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        rows.iter().map(|d| Ok(serde_json::from_str(d)?)).collect()
+    }
+}
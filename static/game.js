@@ -1,6 +1,20 @@
-import init, { wasm_new_game, wasm_make_move, wasm_get_hint } from './pkg/ttt.js';
+import init, {
+  wasm_new_game, wasm_make_move_modeled, wasm_make_move_personality, wasm_get_hints,
+  wasm_render_explanation, wasm_threats, wasm_levels, wasm_set_levels, wasm_personalities,
+  wasm_record_result, wasm_play_local,
+} from './pkg/ttt.js';
 await init();
 
+// Operators can override the built-in level definitions on the server.
+try {
+  const res = await fetch("/api/levels");
+  if (res.ok) wasm_set_levels(await res.text());
+} catch (e) {
+  // Offline: keep the built-in levels.
+}
+const LEVELS = wasm_levels();
+const PERSONALITIES = wasm_personalities();
+
 const WIN_LINES = [
   [0,1,2],[3,4,5],[6,7,8],
   [0,3,6],[1,4,7],[2,5,8],
@@ -14,6 +28,17 @@ let stateHistory = [];
 let assisted = false;
 let hintMove = null;
 let hintText = "";
+let hints = [];
+let hintIndex = 0;
+let dangerSends = new Set();
+// A move the coach warned about; clicking it again plays it anyway.
+let pendingMove = null;
+// Set while playing someone else through a server room: { code, color, token, socket, players, clocks, clockAt }.
+// Spectators have no color or token.
+let online = null;
+// The matchmaking connection while searching for an opponent.
+let queueSocket = null;
+const HINT_COUNT = 3;
 
 // --- Admin storage ---
 function loadAdmin() {
@@ -64,6 +89,10 @@ function syncToPlayer() {
   if (!data) return;
   level = data.level;
   history = data.history || [];
+  stats = data.stats || {};
+  opponent = data.opponent || null;
+  model = data.model || null;
+  coach = data.coach || false;
   state = data.game;
   gameRecorded = data.recorded || false;
   assisted = data.assisted || false;
@@ -71,10 +100,14 @@ function syncToPlayer() {
 }
 
 function syncFromPlayer() {
-  if (!currentPlayer) return;
+  if (!currentPlayer || online) return;
   savePlayerData(currentPlayer, {
     level,
     history,
+    stats,
+    opponent,
+    model,
+    coach,
     game: state,
     recorded: gameRecorded,
     assisted,
@@ -82,10 +115,97 @@ function syncFromPlayer() {
   });
 }
 
+// --- Account ---
+// Set while signed in to the server: { token, account: { username, role, household } }.
+let session = JSON.parse(localStorage.getItem("ttt_session") || "null");
+
+function saveSession(s) {
+  session = s;
+  if (s) localStorage.setItem("ttt_session", JSON.stringify(s));
+  else localStorage.removeItem("ttt_session");
+}
+
+function authHeaders() {
+  const headers = { "Content-Type": "application/json" };
+  if (session) headers.Authorization = "Bearer " + session.token;
+  return headers;
+}
+
+// --- Server sync ---
+// While signed in, progress is mirrored to the server so it survives a cleared browser and follows the
+// player to other devices. Every call is best effort: a static deployment simply keeps localStorage.
+function playerProgress(data) {
+  return { level: data.level || 0, history: data.history || [], ...(data.stats || {}) };
+}
+
+function fromProgress(p) {
+  return {
+    level: p.level,
+    history: p.history,
+    stats: { streak: p.streak, best_streak: p.best_streak, highest_level: p.highest_level, games: p.games, rating: p.rating },
+  };
+}
+
+function playerUrl(name) {
+  return "/api/players/" + encodeURIComponent(name);
+}
+
+async function pushPlayer(name) {
+  if (!session) return;
+  const progress = playerProgress(loadPlayerData(name));
+  try {
+    const res = await fetch(playerUrl(name), { method: "PUT", headers: authHeaders(), body: JSON.stringify(progress) });
+    if (res.status === 404) {
+      await fetch("/api/players", { method: "POST", headers: authHeaders(), body: JSON.stringify({ name, progress }) });
+    }
+  } catch (e) {}
+}
+
+async function fetchPlayer(name) {
+  try {
+    const res = await fetch(playerUrl(name));
+    return res.ok ? await res.json() : null;
+  } catch (e) {
+    return null;
+  }
+}
+
+// Returns the server's reason for refusing a change, or null if it was stored or there is nowhere to store it.
+async function serverChange(url, method, body) {
+  if (!session) return null;
+  try {
+    const res = await fetch(url, { method, headers: authHeaders(), body: body && JSON.stringify(body) });
+    if (res.ok || res.status === 404) return null;
+    return (await res.json()).error || "The server refused the change";
+  } catch (e) {
+    return null;
+  }
+}
+
+// Server copies of the household's profiles replace the local ones' progress; local boards are kept.
+async function loadHousehold() {
+  try {
+    const res = await fetch("/api/household", { headers: authHeaders() });
+    if (!res.ok) return;
+    const players = loadPlayers();
+    for (const p of await res.json()) {
+      players[p.name] = { ...(players[p.name] || { game: null, recorded: false }), ...fromProgress(p.progress) };
+    }
+    savePlayers(players);
+  } catch (e) {}
+}
+
 // --- Game state ---
-const MAX_LEVEL = 21;
+const MAX_LEVEL = LEVELS.length - 1;
 let level = 0;
 let history = [];
+let stats = {};
+// null plays the level ladder; HUMAN is pass-and-play on this device; otherwise the id of a personality bot.
+const HUMAN = "human";
+let opponent = null;
+// Learned habits of this player, used by the searching levels to anticipate replies.
+let model = null;
+let coach = false;
 
 let prevBoardWinners = null;
 
@@ -97,35 +217,71 @@ const undoBtn = document.getElementById("undo");
 const continueBtn = document.getElementById("continue-btn");
 const hintBtn = document.getElementById("hint");
 const levelEl = document.getElementById("level");
+const coachBtn = document.getElementById("coach");
 const playerNameEl = document.getElementById("player-name");
 
 const playersList = document.getElementById("players-list");
 const newPlayerBtn = document.getElementById("new-player-btn");
+const onlineBtn = document.getElementById("online-btn");
+const matchBtn = document.getElementById("match-btn");
+const watchBtn = document.getElementById("watch-btn");
+const accountBtn = document.getElementById("account-btn");
+const accountForm = document.getElementById("account-form");
+const accountUsername = document.getElementById("account-username");
+const accountPassword = document.getElementById("account-password");
+const accountError = document.getElementById("account-error");
+
+function currentPersonality() {
+  return PERSONALITIES.find(p => p.id === opponent) || null;
+}
 
 function updateLevelDisplay() {
-  if (levelEl) levelEl.textContent = "Level " + level;
+  if (!levelEl) return;
+  if (online) {
+    levelEl.textContent = (online.color ? "Room " : "Watching ") + online.code;
+    levelEl.title = "Click to leave the room";
+    return;
+  }
+  if (opponent === HUMAN) {
+    levelEl.textContent = "Two Players";
+    levelEl.title = "Pass and play: take turns on this device";
+    return;
+  }
+  const personality = currentPersonality();
+  const def = personality || LEVELS[Math.min(level, MAX_LEVEL)];
+  levelEl.textContent = personality ? personality.name : "Level " + level;
+  levelEl.title = def ? def.name + ": " + def.description : "";
+}
+
+function cycleOpponent() {
+  if (online) {
+    if (confirm("Leave room " + online.code + "?")) leaveOnline();
+    return;
+  }
+  const ids = [null, ...PERSONALITIES.map(p => p.id), HUMAN];
+  opponent = ids[(ids.indexOf(opponent) + 1) % ids.length];
+  syncFromPlayer();
+  updateLevelDisplay();
 }
 
 function updatePlayerNameDisplay() {
   if (playerNameEl) playerNameEl.textContent = currentPlayer || "";
 }
 
+// Promotion and demotion rules live in the crate (PlayerProgress). Personality games are rated but don't move the ladder.
 function recordResult(winner) {
-  if (assisted) {
-    syncFromPlayer();
-    return;
-  }
-  history.push(winner);
-  if (history.length > 2) history = history.slice(-2);
-
-  if (history.length === 2 && history.every(r => r === "blue")) {
-    level++;
-    history = [];
-  } else if (history.length === 2 && history.every(r => r === "red")) {
-    if (level > 0) level--;
-    history = [];
-  }
+  const progress = wasm_record_result({ level, history, ...stats }, winner, assisted, state.handicap || null, opponent);
+  level = progress.level;
+  history = progress.history;
+  stats = {
+    streak: progress.streak,
+    best_streak: progress.best_streak,
+    highest_level: progress.highest_level,
+    games: progress.games,
+    rating: progress.rating,
+  };
   syncFromPlayer();
+  if (currentPlayer) pushPlayer(currentPlayer);
 }
 
 // --- Board ---
@@ -188,11 +344,11 @@ function isBoardDead(boardCells) {
 function render() {
   if (!state) return;
 
-  const legal = state.status === "bluetomove"
+  const myTurn = isMyTurn();
+  const legal = myTurn
     ? new Set(getLegalMoves(state).map(([b,c]) => `${b},${c}`))
     : new Set();
 
-  const isBluesTurn = state.status === "bluetomove";
   const gameWon = state.status === "bluewins" || state.status === "redwins";
   const metaWinLine = gameWon ? findWinLine(state.board_winners) : null;
 
@@ -205,9 +361,9 @@ function render() {
 
     if (metaWinLine && metaWinLine.includes(b)) {
       boardEl.classList.add("meta-winner");
-    } else if (isBluesTurn && state.required_board !== null && state.required_board !== undefined && state.required_board === b) {
+    } else if (myTurn && state.required_board !== null && state.required_board !== undefined && state.required_board === b) {
       boardEl.classList.add("active");
-    } else if (isBluesTurn && (state.required_board === null || state.required_board === undefined) && !state.board_full[b]) {
+    } else if (myTurn && (state.required_board === null || state.required_board === undefined) && !state.board_full[b]) {
       boardEl.classList.add("active");
     }
 
@@ -221,6 +377,8 @@ function render() {
       if (legal.has(`${b},${c}`)) el.classList.add("legal");
 
       if (hintMove && hintMove.board === b && hintMove.cell === c) el.classList.add("hint");
+      else if (pendingMove && pendingMove.board === b && pendingMove.cell === c) el.classList.add("danger");
+      else if (hintMove && legal.has(`${b},${c}`) && dangerSends.has(`${b},${c}`)) el.classList.add("danger");
 
       if (metaWinLine && !metaWinLine.includes(b)) el.classList.add("dimmed");
       else if (winLine && !winLine.includes(c) && state.cells[b][c] !== "empty") el.classList.add("dimmed");
@@ -245,14 +403,17 @@ function render() {
   }
   prevBoardWinners = [...state.board_winners];
 
+  coachBtn.classList.toggle("on", coach);
+
   const hintTextEl = document.getElementById("hint-text");
-  if (hintTextEl) hintTextEl.textContent = hintText;
+  if (hintTextEl) hintTextEl.textContent = hintText || statusText();
 
   const gameOver = state.status === "bluewins" || state.status === "redwins" || state.status === "draw";
-  undoBtn.classList.toggle("btn-hidden", gameOver || stateHistory.length === 0);
-  hintBtn.classList.toggle("btn-hidden", gameOver);
-  continueBtn.classList.toggle("btn-hidden", !gameOver);
-  if (gameOver && !gameRecorded) {
+  undoBtn.classList.toggle("btn-hidden", gameOver || stateHistory.length === 0 || !!online);
+  hintBtn.classList.toggle("btn-hidden", gameOver || !!online);
+  continueBtn.classList.toggle("btn-hidden", !gameOver || !!online);
+  // Games against other people don't move the ladder.
+  if (gameOver && !gameRecorded && !online && opponent !== HUMAN) {
     gameRecorded = true;
     const winner = state.status === "bluewins" ? "blue" : state.status === "redwins" ? "red" : "draw";
     recordResult(winner);
@@ -260,37 +421,125 @@ function render() {
   }
 }
 
+function isMyTurn() {
+  if (!state) return false;
+  if (opponent === HUMAN && !online) return state.status === "bluetomove" || state.status === "redtomove";
+  return state.status === (online ? online.color : "blue") + "tomove";
+}
+
+function statusText() {
+  if (!state) return "";
+  const gameOver = state.status === "bluewins" || state.status === "redwins" || state.status === "draw";
+  if (gameOver) return "";
+  if (opponent === HUMAN && !online) return sideName(state.status === "redtomove" ? "red" : "blue") + " to move";
+  if (!online) return "";
+  if (!online.players.every(Boolean)) return "Waiting for an opponent. Invite code: " + online.code;
+  const turn = !online.color ? sideName(state.status === "redtomove" ? "red" : "blue") + " to move"
+    : isMyTurn() ? "Your turn" : "Opponent's turn";
+  return online.clocks ? turn + " · " + clockText() : turn;
+}
+
+// Server clocks are sent with each move; the side to move's time is counted down locally in between.
+function clockText() {
+  const elapsed = Date.now() - online.clockAt;
+  const running = state.status === "bluetomove" ? 0 : state.status === "redtomove" ? 1 : -1;
+  const show = (i) => {
+    const secs = Math.ceil(Math.max(0, online.clocks[i] - (i === running ? elapsed : 0)) / 1000);
+    return Math.floor(secs / 60) + ":" + String(secs % 60).padStart(2, "0");
+  };
+  if (!online.color) return "Green " + show(0) + " · Red " + show(1);
+  const me = online.color === "red" ? 1 : 0;
+  return "You " + show(me) + " · Opponent " + show(1 - me);
+}
+
+function sideName(color) {
+  return color === "red" ? "Red" : "Green";
+}
+
+// How a timeout or resignation reads to whoever is looking at the board.
+function endedBy(color, mine, theirs, watched) {
+  if (!online.color) return sideName(color) + " " + watched;
+  return color === online.color ? mine : theirs;
+}
+
 function onCellClick(e) {
-  if (!state || state.status !== "bluetomove") return;
+  if (!isMyTurn()) return;
   const b = parseInt(e.target.dataset.board);
   const c = parseInt(e.target.dataset.cell);
 
   const legal = getLegalMoves(state);
   if (!legal.some(([lb, lc]) => lb === b && lc === c)) return;
 
+  // The server checks the move and sends it back to both players.
+  if (online) {
+    online.socket.send(JSON.stringify({ type: "move", board_idx: b, cell_idx: c }));
+    return;
+  }
+
+  const confirmed = pendingMove && pendingMove.board === b && pendingMove.cell === c;
+  const check = coach && !confirmed;
+  pendingMove = null;
   hintMove = null;
   hintText = "";
-  stateHistory.push(JSON.parse(JSON.stringify(state)));
-  const result = wasm_make_move(state, b, c, level);
+  const before = JSON.parse(JSON.stringify(state));
+  const result = opponent === HUMAN
+    ? wasm_play_local(state, b, c, check)
+    : opponent
+    ? wasm_make_move_personality(state, b, c, opponent, check)
+    : wasm_make_move_modeled(state, b, c, level, model, check);
+  if (result.warning) {
+    pendingMove = { board: b, cell: c };
+    hintText = wasm_render_explanation([result.warning], navigator.language || "en") + ". Tap again to play it anyway.";
+    render();
+    return;
+  }
   if (result.ok) {
+    stateHistory.push(before);
     state = result.state;
+    if (result.model) model = result.model;
     render();
     syncFromPlayer();
   }
 }
 
+function toggleCoach() {
+  coach = !coach;
+  pendingMove = null;
+  hintText = "";
+  syncFromPlayer();
+  render();
+}
+
+function hintStrength() {
+  if (level < 7) return "beginner";
+  if (level < 14) return "standard";
+  return "advanced";
+}
+
 function hint() {
-  if (!state || state.status !== "bluetomove") return;
+  if (online || !isMyTurn()) return;
   assisted = true;
   syncFromPlayer();
-  const data = wasm_get_hint(state);
+  if (hintMove && hints.length > 0) {
+    hintIndex = (hintIndex + 1) % hints.length;
+  } else {
+    hints = wasm_get_hints(state, HINT_COUNT, hintStrength());
+    hintIndex = 0;
+    const side = state.status === "redtomove" ? "red" : "blue";
+    dangerSends = new Set(wasm_threats(state)[side].dangerous_sends.map(([b, c]) => `${b},${c}`));
+  }
+  if (hints.length === 0) return;
+  const data = hints[hintIndex];
+  const explanation = wasm_render_explanation(data.explanation, navigator.language || "en");
   hintMove = { board: data.board_idx, cell: data.cell_idx };
-  hintText = data.explanation;
+  hintText = hints.length > 1
+    ? (hintIndex + 1) + "/" + hints.length + ": " + explanation
+    : explanation;
   render();
 }
 
 function undo() {
-  if (stateHistory.length === 0) return;
+  if (online || stateHistory.length === 0) return;
   state = stateHistory.pop();
   assisted = true;
   hintMove = null;
@@ -372,12 +621,15 @@ function clearPlayerBoard(name) {
 }
 
 // --- Admin actions ---
-function renamePlayer(oldName) {
+// When signed in, the server has the final say; a refused change is not made locally either.
+async function renamePlayer(oldName) {
   const newName = prompt("Rename " + oldName + " to:", oldName);
   if (!newName || !newName.trim() || newName.trim() === oldName) return;
   const trimmed = newName.trim();
+  if (loadPlayers()[trimmed]) { alert("A player named " + trimmed + " already exists."); return; }
+  const refused = await serverChange(playerUrl(oldName) + "/rename", "POST", { name: trimmed });
+  if (refused) { alert(refused); return; }
   const players = loadPlayers();
-  if (players[trimmed]) { alert("A player named " + trimmed + " already exists."); return; }
   players[trimmed] = players[oldName];
   delete players[oldName];
   savePlayers(players);
@@ -390,12 +642,16 @@ function renamePlayer(oldName) {
   renderPlayersList();
 }
 
-function resetPlayer(name) {
+async function resetPlayer(name) {
   if (!confirm("Reset " + name + " to level 0?")) return;
+  const refused = await serverChange(playerUrl(name), "PUT", playerProgress({}));
+  if (refused) { alert(refused); return; }
   const players = loadPlayers();
   if (!players[name]) return;
   players[name].level = 0;
   players[name].history = [];
+  players[name].stats = {};
+  players[name].model = null;
   players[name].game = null;
   players[name].recorded = false;
   players[name].assisted = false;
@@ -408,8 +664,10 @@ function resetPlayer(name) {
   renderPlayersList();
 }
 
-function deletePlayer(name) {
+async function deletePlayer(name) {
   if (!confirm("Delete " + name + " and all their data?")) return;
+  const refused = await serverChange(playerUrl(name), "DELETE");
+  if (refused) { alert(refused); return; }
   const players = loadPlayers();
   delete players[name];
   savePlayers(players);
@@ -422,6 +680,192 @@ function deletePlayer(name) {
   renderPlayersList();
 }
 
+// --- Sign in ---
+function toggleAccount() {
+  if (session) {
+    if (!confirm("Sign out " + session.account.username + "?")) return;
+    fetch("/api/sessions", { method: "DELETE", headers: authHeaders() }).catch(() => {});
+    saveSession(null);
+    renderPlayersList();
+    return;
+  }
+  accountForm.classList.toggle("hidden");
+  accountError.textContent = "";
+  accountUsername.focus();
+}
+
+// Creating a household also uploads the profiles already on this device into it.
+async function submitAccount(register) {
+  const username = accountUsername.value.trim();
+  const password = accountPassword.value;
+  if (!username || !password) return;
+  try {
+    const res = await fetch(register ? "/api/accounts" : "/api/sessions", {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({ username, password }),
+    });
+    const body = await res.json();
+    if (!res.ok) {
+      accountError.textContent = body.error || "Could not sign in";
+      return;
+    }
+    saveSession({ token: body.token, account: body.account });
+  } catch (e) {
+    accountError.textContent = "Could not reach the server";
+    return;
+  }
+  accountPassword.value = "";
+  accountForm.classList.add("hidden");
+  if (register) {
+    for (const name of Object.keys(loadPlayers())) await pushPlayer(name);
+  }
+  await loadHousehold();
+  selectPlayer(session.account.username);
+}
+
+// --- Online rooms ---
+async function startOnline() {
+  const input = prompt("Enter an invite code to join a game, or leave it empty to start one:");
+  if (input === null) return;
+  const code = input.trim().toUpperCase();
+  // Signed in, the game is recorded for this player.
+  const res = await fetch(code ? "/api/rooms/" + encodeURIComponent(code) + "/join" : "/api/rooms", { method: "POST", headers: authHeaders() });
+  const body = await res.json();
+  if (!res.ok) {
+    alert(body.error || "Could not reach the server");
+    return;
+  }
+  connectRoom(body);
+}
+
+function connectRoom(seat) {
+  closeRoom();
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  const path = seat.token ? "/ws?token=" + seat.token : "/watch";
+  const socket = new WebSocket(scheme + "://" + location.host + "/api/rooms/" + seat.code + path);
+  const ticker = setInterval(() => { if (online && online.clocks) render(); }, 1000);
+  online = { ...seat, socket, players: [true, false], clocks: null, clockAt: 0, ticker };
+  state = null;
+  hintMove = null;
+  pendingMove = null;
+  prevBoardWinners = null;
+  socket.onmessage = (msg) => {
+    const ev = JSON.parse(msg.data);
+    hintText = "";
+    if (ev.clocks) {
+      online.clocks = ev.clocks;
+      online.clockAt = Date.now();
+    }
+    if (ev.type === "state") {
+      state = ev.state;
+      online.players = ev.players;
+    } else if (ev.type === "joined") {
+      online.players[ev.color === "red" ? 1 : 0] = true;
+    } else if (ev.type === "move") {
+      state = ev.state;
+    } else if (ev.type === "timeout") {
+      state = ev.state;
+      hintText = endedBy(ev.loser, "You ran out of time", "Your opponent ran out of time", "ran out of time");
+    } else if (ev.type === "resigned") {
+      state = ev.state;
+      hintText = endedBy(ev.player, "You resigned", "Your opponent left the game", "left the game");
+    } else if (ev.type === "error") {
+      hintText = ev.message;
+    }
+    render();
+  };
+  // The server closes a room once its game is over, so only an unfinished game has been cut off.
+  socket.onclose = () => {
+    const over = state && state.status !== "bluetomove" && state.status !== "redtomove";
+    if (online && online.socket === socket && !over) {
+      hintText = "Disconnected from room " + online.code;
+      render();
+    }
+  };
+  updateLevelDisplay();
+  showGameView();
+}
+
+function closeRoom() {
+  if (!online) return;
+  const socket = online.socket;
+  clearInterval(online.ticker);
+  online = null;
+  socket.close();
+  hintText = "";
+}
+
+function leaveOnline() {
+  if (!online) return;
+  closeRoom();
+  syncToPlayer();
+  updateLevelDisplay();
+  if (state) render();
+  else newGame();
+}
+
+// Spectators pick from the games under way and follow along without being able to move.
+async function startWatching() {
+  let rooms;
+  try {
+    rooms = await (await fetch("/api/rooms")).json();
+  } catch (e) {
+    alert("Could not reach the server");
+    return;
+  }
+  if (rooms.length === 0) {
+    alert("No one is playing right now.");
+    return;
+  }
+  const names = (r) => (r.names[0] || "Green") + " vs " + (r.names[1] || "Red");
+  const list = rooms.map(r => r.code + ": " + names(r) + ", move " + r.ply).join("\n");
+  const input = prompt("Enter the code of the game to watch:\n" + list, rooms[0].code);
+  if (!input) return;
+  connectRoom({ code: input.trim().toUpperCase(), color: null });
+}
+
+// --- Matchmaking ---
+// Pairs this player with someone of similar rating; after a while the server offers a computer opponent instead.
+function findOpponent() {
+  if (queueSocket) {
+    stopSearching();
+    return;
+  }
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  const query = session ? "?token=" + encodeURIComponent(session.token) : "";
+  const socket = new WebSocket(scheme + "://" + location.host + "/api/matchmaking/ws" + query);
+  queueSocket = socket;
+  matchBtn.textContent = "Searching… (cancel)";
+  socket.onopen = () => socket.send(JSON.stringify({ type: "join" }));
+  socket.onmessage = (msg) => {
+    const ev = JSON.parse(msg.data);
+    if (ev.type === "queued") {
+      matchBtn.textContent = "Searching " + ev.waiting_secs + "s… (cancel)";
+    } else if (ev.type === "bot_offer") {
+      if (confirm("No one is around right now. Play " + ev.name + " instead?")) socket.send(JSON.stringify({ type: "accept_bot" }));
+    } else if (ev.type === "matched") {
+      stopSearching();
+      connectRoom(ev);
+    } else if (ev.type === "error") {
+      alert(ev.message);
+    }
+  };
+  socket.onclose = () => {
+    if (queueSocket === socket) stopSearching();
+  };
+}
+
+function stopSearching() {
+  const socket = queueSocket;
+  queueSocket = null;
+  matchBtn.textContent = "Find Opponent";
+  if (socket && socket.readyState === WebSocket.OPEN) {
+    socket.send(JSON.stringify({ type: "leave" }));
+    socket.close();
+  }
+}
+
 // --- Views ---
 function showGameView() {
   gameView.classList.remove("hidden");
@@ -437,8 +881,9 @@ function showPlayersView() {
 function renderPlayersList() {
   const players = loadPlayers();
   playersList.innerHTML = "";
-  const admin = loadAdmin();
-  const isAdmin = currentPlayer === admin;
+  accountBtn.textContent = session ? "Sign Out " + session.account.username : "Sign In";
+  const admin = session ? session.account.username : loadAdmin();
+  const isAdmin = session ? session.account.role === "admin" : currentPlayer === admin;
   const names = Object.keys(players).sort((a, b) => a.localeCompare(b, undefined, { sensitivity: "base" }));
   for (const name of names) {
     const data = players[name];
@@ -453,6 +898,8 @@ function renderPlayersList() {
     const infoSpan = document.createElement("span");
     infoSpan.className = "player-row-info";
     infoSpan.textContent = "Level " + (data.level || 0);
+    const rating = data.stats && data.stats.rating;
+    if (rating) infoSpan.textContent += " · " + Math.round(rating.rating) + (rating.rd > 110 ? "?" : "");
 
     const clearBtn = document.createElement("button");
     clearBtn.className = "player-row-clear";
@@ -492,6 +939,7 @@ function renderPlayersList() {
 }
 
 function selectPlayer(name) {
+  closeRoom();
   currentPlayer = name;
   saveCurrentPlayerName(name);
   syncToPlayer();
@@ -508,25 +956,38 @@ function selectPlayer(name) {
   }
 }
 
-function promptNewPlayer() {
+// A name the server already knows picks up that player's progress from another device.
+async function promptNewPlayer() {
   const name = prompt("Enter your name:");
   if (!name || !name.trim()) return;
   const trimmed = name.trim();
   const players = loadPlayers();
   if (!players[trimmed]) {
-    players[trimmed] = { level: 0, history: [], game: null, recorded: false };
+    const stored = await fetchPlayer(trimmed);
+    players[trimmed] = stored
+      ? { ...fromProgress(stored.progress), game: null, recorded: false }
+      : { level: 0, history: [], game: null, recorded: false };
     savePlayers(players);
     if (!loadAdmin()) saveAdmin(trimmed);
+    if (!stored) pushPlayer(trimmed);
   }
   selectPlayer(trimmed);
 }
 
 // --- Init ---
 playerNameEl.addEventListener("click", showPlayersView);
+levelEl.addEventListener("click", cycleOpponent);
+coachBtn.addEventListener("click", toggleCoach);
 undoBtn.addEventListener("click", undo);
 hintBtn.addEventListener("click", hint);
 continueBtn.addEventListener("click", moreGame);
 newPlayerBtn.addEventListener("click", promptNewPlayer);
+onlineBtn.addEventListener("click", startOnline);
+matchBtn.addEventListener("click", findOpponent);
+watchBtn.addEventListener("click", startWatching);
+accountBtn.addEventListener("click", toggleAccount);
+accountForm.addEventListener("submit", (e) => { e.preventDefault(); submitAccount(false); });
+document.getElementById("account-register").addEventListener("click", () => submitAccount(true));
 document.getElementById("level-count").textContent = MAX_LEVEL + 1;
 buildBoard();
 
@@ -1,4 +1,4 @@
-const CACHE = 'ttt-v1';
+const CACHE = 'ttt-v11';
 const ASSETS = [
   '/',
   '/game.js',
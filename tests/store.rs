@@ -0,0 +1,187 @@
+// Both stores are run through the same checks, so they can't drift apart.
+use ttt::progress::Outcome;
+use ttt::server::correspondence::Correspondence;
+use ttt::server::games::{Game, GameOptions, Rules};
+use ttt::server::store::{
+    Account, GameRecord, MemoryStore, Opponent, PlayerRecord, Repository, Role, Session, SqliteStore, StoreError,
+};
+use ttt::{Cell, GameState};
+
+fn stores() -> Vec<(&'static str, Box<dyn Repository>)> {
+    vec![
+        ("memory", Box::new(MemoryStore::new())),
+        ("sqlite", Box::new(SqliteStore::in_memory().unwrap())),
+    ]
+}
+
+fn player(name: &str) -> PlayerRecord {
+    PlayerRecord { name: name.into(), progress: Default::default(), created: 1, household: Some("home".into()) }
+}
+
+fn record(id: &str, player: &str, opponent: &str) -> GameRecord {
+    GameRecord {
+        id: id.into(),
+        player: player.into(),
+        color: Cell::Blue,
+        opponent: Opponent::Human { name: Some(opponent.into()) },
+        outcome: Outcome::Win,
+        moves: vec![(4, 4)],
+        plies: 1,
+        assisted: false,
+        handicap: None,
+        rating: None,
+        finished: 2,
+    }
+}
+
+fn correspondence(id: &str, players: [&str; 2], deadline: Option<u64>) -> Correspondence {
+    Correspondence {
+        id: id.into(),
+        players: players.map(String::from),
+        rules: Rules::default(),
+        move_days: 3,
+        state: GameState::new(),
+        moves: Vec::new(),
+        invited: None,
+        deadline,
+        ended: None,
+        created: 1,
+    }
+}
+
+// Alice with an account, a session, a record, a saved game and a correspondence game against Bob.
+fn seed(store: &dyn Repository) -> Game {
+    store.create_player(&player("alice")).unwrap();
+    store.create_player(&player("bob")).unwrap();
+    store.create_account(&Account {
+        username: "alice".into(),
+        password_hash: "hash".into(),
+        role: Role::Admin,
+        household: "home".into(),
+        created: 1,
+    })
+    .unwrap();
+    store.create_session(&Session { token: "t1".into(), username: "alice".into(), expires: 100 }).unwrap();
+    store.add_record(&record("r1", "alice", "bob")).unwrap();
+    store.add_record(&record("r2", "bob", "alice")).unwrap();
+    let game = Game::new(GameOptions { player: Some("alice".into()), ..Default::default() }).unwrap();
+    store.save_game(&game).unwrap();
+    store.save_correspondence(&correspondence("c1", ["alice", "bob"], Some(50))).unwrap();
+    game
+}
+
+#[test]
+fn duplicate_names_are_refused() {
+    for (kind, store) in stores() {
+        seed(store.as_ref());
+        assert!(matches!(store.create_player(&player("alice")), Err(StoreError::AlreadyExists)), "{}", kind);
+        assert!(matches!(store.rename_player("alice", "bob"), Err(StoreError::AlreadyExists)), "{}", kind);
+        assert!(matches!(store.rename_player("nobody", "carol"), Err(StoreError::NotFound)), "{}", kind);
+    }
+}
+
+#[test]
+fn rename_carries_everything_over() {
+    for (kind, store) in stores() {
+        let game = seed(store.as_ref());
+        store.rename_player("alice", "carol").unwrap();
+
+        assert!(store.player("alice").unwrap().is_none(), "{}", kind);
+        assert_eq!(store.player("carol").unwrap().unwrap().name, "carol", "{}", kind);
+        assert!(store.account("alice").unwrap().is_none(), "{}", kind);
+        assert_eq!(store.account("carol").unwrap().unwrap().username, "carol", "{}", kind);
+        assert_eq!(store.session("t1").unwrap().unwrap().username, "carol", "{}", kind);
+
+        assert!(store.records("alice").unwrap().is_empty(), "{}", kind);
+        let records = store.records("carol").unwrap();
+        assert_eq!(records.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), ["r1"], "{}", kind);
+        // Bob's record still names the opponent as they were at the time.
+        assert_eq!(store.records("bob").unwrap().len(), 1, "{}", kind);
+
+        assert_eq!(store.game(&game.id).unwrap().unwrap().player.as_deref(), Some("carol"), "{}", kind);
+        assert_eq!(store.games_of("carol").unwrap().len(), 1, "{}", kind);
+        assert!(store.games_of("alice").unwrap().is_empty(), "{}", kind);
+
+        assert_eq!(store.correspondence("c1").unwrap().unwrap().players, ["carol", "bob"], "{}", kind);
+        assert_eq!(store.correspondence_of("carol").unwrap().len(), 1, "{}", kind);
+        assert!(store.correspondence_of("alice").unwrap().is_empty(), "{}", kind);
+    }
+}
+
+#[test]
+fn delete_removes_everything() {
+    for (kind, store) in stores() {
+        let game = seed(store.as_ref());
+        store.delete_player("alice").unwrap();
+
+        assert!(store.player("alice").unwrap().is_none(), "{}", kind);
+        assert!(store.account("alice").unwrap().is_none(), "{}", kind);
+        assert!(store.session("t1").unwrap().is_none(), "{}", kind);
+        assert!(store.records("alice").unwrap().is_empty(), "{}", kind);
+        assert!(store.game(&game.id).unwrap().is_none(), "{}", kind);
+        // A correspondence game can't go on without one of its players.
+        assert!(store.correspondence("c1").unwrap().is_none(), "{}", kind);
+        assert!(store.correspondence_of("bob").unwrap().is_empty(), "{}", kind);
+
+        assert!(store.player("bob").unwrap().is_some(), "{}", kind);
+        assert_eq!(store.records("bob").unwrap().len(), 1, "{}", kind);
+        assert!(matches!(store.delete_player("alice"), Err(StoreError::NotFound)), "{}", kind);
+    }
+}
+
+#[test]
+fn overdue_correspondence_skips_finished_and_future_games() {
+    for (kind, store) in stores() {
+        seed(store.as_ref());
+        store.save_correspondence(&correspondence("c2", ["bob", "alice"], Some(200))).unwrap();
+        store.save_correspondence(&correspondence("c3", ["bob", "alice"], None)).unwrap();
+
+        let ids = |at| {
+            let mut ids: Vec<String> = store.overdue_correspondence(at).unwrap().into_iter().map(|g| g.id).collect();
+            ids.sort();
+            ids
+        };
+        assert!(ids(49).is_empty(), "{}", kind);
+        assert_eq!(ids(50), ["c1"], "{}", kind);
+        assert_eq!(ids(300), ["c1", "c2"], "{}", kind);
+    }
+}
+
+#[test]
+fn records_come_back_oldest_first() {
+    for (kind, store) in stores() {
+        store.create_player(&player("alice")).unwrap();
+        for (i, finished) in [5, 3, 9].into_iter().enumerate() {
+            store.add_record(&GameRecord { finished, ..record(&format!("r{}", i), "alice", "bob") }).unwrap();
+        }
+        let finished: Vec<u64> = store.records("alice").unwrap().iter().map(|r| r.finished).collect();
+        assert_eq!(finished, [3, 5, 9], "{}", kind);
+    }
+}
+
+#[test]
+fn bot_registration_is_all_or_nothing() {
+    for (kind, store) in stores() {
+        seed(store.as_ref());
+        let bot = |name: &str, token: &str| {
+            let account = Account {
+                username: name.into(),
+                password_hash: String::new(),
+                role: Role::Bot,
+                household: "home".into(),
+                created: 1,
+            };
+            let session = Session { token: token.into(), username: name.into(), expires: 100 };
+            store.create_bot(&player(name), &account, &session)
+        };
+        // Bob is a player without an account; neither half of a clash may be left behind.
+        assert!(matches!(bot("bob", "t2"), Err(StoreError::AlreadyExists)), "{}", kind);
+        assert!(store.account("bob").unwrap().is_none(), "{}", kind);
+        assert!(store.session("t2").unwrap().is_none(), "{}", kind);
+
+        bot("engine", "t3").unwrap();
+        assert!(store.player("engine").unwrap().is_some(), "{}", kind);
+        assert_eq!(store.account("engine").unwrap().unwrap().role, Role::Bot, "{}", kind);
+        assert_eq!(store.session("t3").unwrap().unwrap().username, "engine", "{}", kind);
+    }
+}